@@ -0,0 +1,97 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use super::state::{App, PagerSource, PreviewState};
+
+// Editor used when neither $VISUAL nor $EDITOR is set
+const DEFAULT_EDITOR: &str = "vi";
+
+// Editors known to accept `+N` to open a file at line N
+const LINE_FLAG_EDITORS: &[&str] = &[
+    "vi", "vim", "nvim", "gvim", "nano", "emacs", "emacsclient", "micro", "kak", "joe", "ne", "mg",
+];
+
+// A file to open in the user's editor. The main loop suspends the terminal
+// while it runs.
+pub struct EditRequest {
+    pub path: PathBuf,
+    // 1-based line to open the file at
+    pub line: usize,
+}
+
+impl EditRequest {
+    // Build the command from $VISUAL or $EDITOR, which may carry arguments
+    // (e.g. "code --wait")
+    pub fn command(&self) -> Command {
+        let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or(DEFAULT_EDITOR);
+        let mut command = Command::new(program);
+        command.args(parts);
+        if supports_line_flag(program) {
+            command.arg(format!("+{}", self.line));
+        }
+        command.arg(&self.path);
+        // With Markdown piped in (`greenmerald -`) our stdin is the spent
+        // pipe; give the editor the terminal instead
+        if !io::stdin().is_terminal()
+            && let Ok(tty) = File::open("/dev/tty")
+        {
+            command.stdin(tty);
+        }
+        command
+    }
+}
+
+fn supports_line_flag(program: &str) -> bool {
+    Path::new(program)
+    .file_name()
+    .and_then(|name| name.to_str())
+    .is_some_and(|name| LINE_FLAG_EDITORS.contains(&name))
+}
+
+impl App {
+    // What to open for the edit key: the previewed file, at the source line
+    // shown at the top of the preview
+    pub fn edit_request(&mut self) -> Option<EditRequest> {
+        let path = self.previewed_path().filter(|path| path.is_file());
+        let Some(path) = path else {
+            self.status_message = Some(if self.pager == Some(PagerSource::Stdin) {
+                "Standard input cannot be edited".to_string()
+            } else {
+                "Select a file to edit".to_string()
+            });
+            return None;
+        };
+        let line = match &self.preview_content {
+            PreviewState::Markdown(doc) => doc.source_line_at(self.preview_scroll_offset as usize) + 1,
+            _ => 1,
+        };
+        Some(EditRequest { path, line })
+    }
+
+    // Back from the editor: pick up the changes, staying where we were
+    pub fn finish_edit(&mut self, request: &EditRequest, status: io::Result<ExitStatus>) {
+        match status {
+            Ok(status) if !status.success() => {
+                self.status_message = Some(format!("Editor exited with {status}"));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Could not start editor: {e}"));
+            }
+            Ok(_) => {}
+        }
+        if self.previewed_path().as_deref() == Some(request.path.as_path()) {
+            self.reload_preview();
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use super::state::App;
+use crate::fs::export::{ExportHandle, ExportMessage, default_output, export_file, files_exported, spawn_export_tree};
+
+// A directory export running in the background, and where it writes to
+pub struct Exporting {
+    handle: ExportHandle,
+    output: PathBuf,
+}
+
+impl App {
+    // Export the previewed Markdown file to HTML beside it, or every
+    // Markdown file under the selected directory to `<dir>-html/`. A
+    // directory is exported on a background thread, as `poll_export`
+    // reports.
+    pub fn export_selected(&mut self) {
+        let Some(path) = self.previewed_path() else {
+            self.status_message = Some("Nothing to export".to_string());
+            return;
+        };
+        let output = default_output(&path);
+        if path.is_dir() {
+            self.status_message = Some(format!("Exporting to {}...", output.display()));
+            // Replacing an export still running cancels it
+            self.exporting = Some(Exporting {
+                handle: spawn_export_tree(&path, &output, self.theme.clone(), self.list_options),
+                output,
+            });
+        } else if path.is_file() && self.is_markdown_path(&path) {
+            let result = export_file(&path, &output, &self.theme, false)
+            .map(|()| format!("Exported to {}", output.display()));
+            self.status_message = Some(result.unwrap_or_else(|e| format!("Export failed: {e}")));
+        } else {
+            self.status_message = Some("Select a Markdown file or directory to export".to_string());
+        }
+    }
+
+    // Show how far the background export got. Returns true if the status
+    // line changed.
+    pub fn poll_export(&mut self) -> bool {
+        let Some(exporting) = &self.exporting else {
+            return false;
+        };
+        let mut changed = false;
+        let mut finished = false;
+        for message in exporting.handle.receiver.try_iter() {
+            changed = true;
+            self.status_message = Some(match message {
+                ExportMessage::Progress(count) => {
+                    format!("Exporting to {}... {}", exporting.output.display(), files_exported(count))
+                }
+                ExportMessage::Done(result) => {
+                    finished = true;
+                    match result {
+                        Ok(count) => format!("Exported {} to {}", files_exported(count), exporting.output.display()),
+                        Err(e) => format!("Export failed: {e}"),
+                    }
+                }
+            });
+        }
+        if finished {
+            self.exporting = None;
+        }
+        changed
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use ratatui::widgets::ListState;
+
+use super::state::{App, InputMode, PreviewState};
+use crate::fs::grep::{GrepHandle, GrepHit, GrepMessage, spawn_grep};
+
+// A search across every Markdown file under `root`. While it exists its
+// results are listed in place of the Current panel.
+pub struct FileSearch {
+    pub query: String,
+    // Directory the search was started from
+    pub root: PathBuf,
+    pub hits: Vec<GrepHit>,
+    pub selected: ListState,
+    // Set once the worker finished: (files searched, hit limit reached)
+    pub done: Option<(usize, bool)>,
+    // The running worker; dropping it cancels the search
+    handle: Option<GrepHandle>,
+}
+
+impl FileSearch {
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+}
+
+impl App {
+    // Start typing a query for a search across files
+    pub fn start_file_search(&mut self) {
+        self.input_mode = InputMode::FileSearch;
+        self.file_search = Some(FileSearch {
+            query: String::new(),
+            root: self.current_path.clone(),
+            hits: Vec::new(),
+            selected: ListState::default(),
+            done: None,
+            handle: None,
+        });
+    }
+
+    pub fn push_file_search_char(&mut self, c: char) {
+        if let Some(search) = &mut self.file_search {
+            search.query.push(c);
+        }
+    }
+
+    pub fn pop_file_search_char(&mut self) {
+        if let Some(search) = &mut self.file_search {
+            search.query.pop();
+        }
+    }
+
+    // Run the typed query in the background (Enter)
+    pub fn submit_file_search(&mut self) {
+        self.input_mode = InputMode::Normal;
+        match &mut self.file_search {
+            Some(search) if !search.query.is_empty() => {
+                search.handle = Some(spawn_grep(&search.root, &search.query, self.list_options));
+            }
+            _ => self.close_file_search(),
+        }
+    }
+
+    // Drop the results (cancelling a running search) and show the Current
+    // panel again
+    pub fn close_file_search(&mut self) {
+        self.input_mode = InputMode::Normal;
+        if self.file_search.take().is_some() {
+            self.preview_search = None;
+            self.update_preview();
+        }
+    }
+
+    // Collect whatever the worker found since the last call. Returns true if
+    // the results changed.
+    pub fn poll_file_search(&mut self) -> bool {
+        let Some(search) = &mut self.file_search else {
+            return false;
+        };
+        let Some(handle) = &search.handle else {
+            return false;
+        };
+        let mut changed = false;
+        let mut finished = false;
+        for message in handle.receiver.try_iter() {
+            changed = true;
+            match message {
+                GrepMessage::Hit(hit) => search.hits.push(hit),
+                GrepMessage::Done { files, truncated } => {
+                    search.done = Some((files, truncated));
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            search.handle = None;
+        }
+        // Preview the first hit as soon as there is one
+        if search.selected.selected().is_none() && !search.hits.is_empty() {
+            search.selected.select(Some(0));
+            self.preview_selected_hit();
+        }
+        changed
+    }
+
+    // The highlighted search result, if results are listed
+    pub fn selected_hit(&self) -> Option<&GrepHit> {
+        let search = self.file_search.as_ref()?;
+        search.selected.selected().and_then(|i| search.hits.get(i))
+    }
+
+    pub fn select_next_hit(&mut self) {
+        self.step_hit(1);
+    }
+
+    pub fn select_previous_hit(&mut self) {
+        self.step_hit(-1);
+    }
+
+    // Select a result by its position in the list (a mouse click)
+    pub fn select_hit(&mut self, index: usize) {
+        let Some(search) = &mut self.file_search else {
+            return;
+        };
+        if index < search.hits.len() && search.selected.selected() != Some(index) {
+            search.selected.select(Some(index));
+            self.preview_selected_hit();
+        }
+    }
+
+    fn step_hit(&mut self, step: isize) {
+        let Some(search) = &mut self.file_search else {
+            return;
+        };
+        let count = search.hits.len();
+        if count == 0 {
+            return;
+        }
+        let next = match search.selected.selected() {
+            Some(i) => (i as isize + step).rem_euclid(count as isize) as usize,
+            None => 0,
+        };
+        search.selected.select(Some(next));
+        self.preview_selected_hit();
+    }
+
+    // Show the selected hit's file in the preview, scrolled to the matching
+    // line with the query highlighted
+    fn preview_selected_hit(&mut self) {
+        let Some((path, line)) = self.selected_hit().map(|hit| (hit.path.clone(), hit.line)) else {
+            return;
+        };
+        let query = self.file_search.as_ref().map(|search| search.query.clone()).unwrap_or_default();
+        self.preview_markdown_file(&path);
+        if let PreviewState::Markdown(doc) = &self.preview_content {
+            self.preview_scroll_offset = doc.line_for_source(line).min(u16::MAX as usize) as u16;
+        }
+        self.search_preview_for(&query);
+    }
+
+    // Leave the results and browse to the selected hit's file (Enter)
+    pub fn open_selected_hit(&mut self) {
+        let Some(path) = self.selected_hit().map(|hit| hit.path.clone()) else {
+            return;
+        };
+        let offset = self.preview_scroll_offset;
+        self.push_history();
+        let query = self.file_search.take().map(|search| search.query).unwrap_or_default();
+        self.select_path(&path);
+        self.preview_scroll_offset = offset;
+        self.search_preview_for(&query);
+    }
+}
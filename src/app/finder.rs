@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use ratatui::widgets::ListState;
+
+use super::state::{App, InputMode, PreviewState};
+use crate::fs::{PathIndexHandle, spawn_path_index};
+
+// Only the best candidates are ranked into the list
+const MAX_CANDIDATES: usize = 500;
+
+// A path that matched the query
+pub struct Candidate {
+    // Index into `Finder::paths`
+    pub index: usize,
+    pub score: i64,
+    // Char positions of the matched characters, for highlighting
+    pub positions: Vec<usize>,
+}
+
+// The Ctrl+p popup: every path below `root`, fuzzy-ranked against `query`
+pub struct Finder {
+    pub query: String,
+    root: PathBuf,
+    // Paths relative to `root`, as they are shown and matched
+    pub paths: Vec<String>,
+    pub candidates: Vec<Candidate>,
+    pub selected: ListState,
+    // The index while it is still being built; dropping it stops the walk
+    index: Option<PathIndexHandle>,
+    // Whether the index stopped at the `[tree]` entry limit
+    pub truncated: bool,
+    matcher: SkimMatcherV2,
+}
+
+impl Finder {
+    pub fn is_indexing(&self) -> bool {
+        self.index.is_some()
+    }
+
+    fn path(&self, candidate: &Candidate) -> PathBuf {
+        self.root.join(&self.paths[candidate.index])
+    }
+
+    fn rank(&mut self) {
+        if self.query.is_empty() {
+            // Nothing typed yet: list the paths in tree order
+            self.candidates = (0..self.paths.len().min(MAX_CANDIDATES))
+            .map(|index| Candidate { index, score: 0, positions: Vec::new() })
+            .collect();
+        } else {
+            self.candidates = self
+            .paths
+            .iter()
+            .enumerate()
+            .filter_map(|(index, path)| {
+                let (score, positions) = self.matcher.fuzzy_indices(path, &self.query)?;
+                Some(Candidate { index, score, positions })
+            })
+            .collect();
+            // Best score first; shorter paths win ties
+            self.candidates.sort_by(|a, b| {
+                b.score
+                .cmp(&a.score)
+                .then_with(|| self.paths[a.index].len().cmp(&self.paths[b.index].len()))
+            });
+            self.candidates.truncate(MAX_CANDIDATES);
+        }
+        self.selected.select(if self.candidates.is_empty() { None } else { Some(0) });
+    }
+}
+
+impl App {
+    // Open the finder (Ctrl+p) and start indexing in the background
+    pub fn open_finder(&mut self) {
+        self.input_mode = InputMode::Finder;
+        self.finder = Some(Finder {
+            query: String::new(),
+            root: self.current_path.clone(),
+            paths: Vec::new(),
+            candidates: Vec::new(),
+            selected: ListState::default(),
+            index: Some(spawn_path_index(&self.current_path, self.list_options, self.config.tree.max_entries)),
+            truncated: false,
+            matcher: SkimMatcherV2::default(),
+        });
+    }
+
+    // Close the finder without moving (Esc)
+    pub fn close_finder(&mut self) {
+        self.input_mode = InputMode::Normal;
+        if self.finder.take().is_some() {
+            self.update_preview();
+        }
+    }
+
+    // Pick up the index once the background walk finished. Returns true if
+    // it arrived.
+    pub fn poll_finder(&mut self) -> bool {
+        let Some(finder) = &mut self.finder else {
+            return false;
+        };
+        let Some(index) = finder.index.as_ref().and_then(|index| index.receiver.try_recv().ok()) else {
+            return false;
+        };
+        finder.index = None;
+        finder.truncated = index.truncated;
+        finder.paths = index
+        .paths
+        .iter()
+        .map(|path| path.strip_prefix(&finder.root).unwrap_or(path).to_string_lossy().into_owned())
+        .collect();
+        finder.rank();
+        self.preview_candidate();
+        true
+    }
+
+    pub fn push_finder_char(&mut self, c: char) {
+        if let Some(finder) = &mut self.finder {
+            finder.query.push(c);
+            finder.rank();
+        }
+        self.preview_candidate();
+    }
+
+    pub fn pop_finder_char(&mut self) {
+        if let Some(finder) = &mut self.finder {
+            finder.query.pop();
+            finder.rank();
+        }
+        self.preview_candidate();
+    }
+
+    pub fn select_next_candidate(&mut self) {
+        self.step_candidate(1);
+    }
+
+    pub fn select_previous_candidate(&mut self) {
+        self.step_candidate(-1);
+    }
+
+    fn step_candidate(&mut self, step: isize) {
+        let Some(finder) = &mut self.finder else {
+            return;
+        };
+        let count = finder.candidates.len();
+        if count == 0 {
+            return;
+        }
+        let next = match finder.selected.selected() {
+            Some(i) => (i as isize + step).rem_euclid(count as isize) as usize,
+            None => 0,
+        };
+        finder.selected.select(Some(next));
+        self.preview_candidate();
+    }
+
+    // Full path of the highlighted candidate
+    pub fn selected_candidate(&self) -> Option<PathBuf> {
+        let finder = self.finder.as_ref()?;
+        let candidate = finder.candidates.get(finder.selected.selected()?)?;
+        Some(finder.path(candidate))
+    }
+
+    // Show the highlighted candidate in the preview as it changes
+    fn preview_candidate(&mut self) {
+        match self.selected_candidate() {
+            Some(path) => self.preview_path(&path),
+            None => {
+                self.preview_scroll_offset = 0;
+                self.preview_content = PreviewState::None;
+            }
+        }
+    }
+
+    // Browse to the highlighted candidate: its directory becomes the current
+    // one with the candidate selected (Enter)
+    pub fn open_selected_candidate(&mut self) {
+        let Some(path) = self.selected_candidate() else {
+            return;
+        };
+        self.input_mode = InputMode::Normal;
+        self.finder = None;
+        // Search results would hide the directory we are about to show
+        self.file_search = None;
+        self.push_history();
+        self.select_path(&path);
+    }
+}
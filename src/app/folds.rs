@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use super::state::{App, InputMode};
+
+// How many roots are saved between sessions, the most recently shown first
+const MAX_SAVED_ROOTS: usize = 50;
+
+// ## Folds ##
+// Which directories the recursive view shows open, kept for every directory
+// it was shown from. A refresh, switching views or coming back from
+// elsewhere shows the same tree again, and with `tree.remember_folds` so
+// does the next session.
+
+#[derive(Default)]
+pub struct Folds {
+    roots: HashMap<PathBuf, RootFolds>,
+    // Counts up each time a tree view is shown, to tell which roots were
+    // used last
+    clock: u64,
+}
+
+// The folds of the tree view shown from one directory. Only what differs
+// from its default is kept: with nothing opened by hand, every directory is
+// folded.
+#[derive(Default)]
+struct RootFolds {
+    // Directories fewer than this many levels down are open unless toggled
+    // ("expand all" and "collapse to depth" set it)
+    depth: usize,
+    // Directories opened or folded by hand against that
+    toggled: HashSet<PathBuf>,
+    used: u64,
+}
+
+impl RootFolds {
+    fn opens_by_default(&self, root: &Path, dir: &Path) -> bool {
+        levels(root, dir) < self.depth
+    }
+}
+
+// How many levels below `root` a directory is, its own entries being 1
+fn levels(root: &Path, dir: &Path) -> usize {
+    dir.strip_prefix(root).map_or(usize::MAX, |path| path.components().count())
+}
+
+// folds.toml: one `[[root]]` table per root, directories relative to it
+#[derive(Default, Serialize, Deserialize)]
+struct SavedFolds {
+    #[serde(default, rename = "root")]
+    roots: Vec<SavedRoot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRoot {
+    path: PathBuf,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    depth: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    toggled: Vec<PathBuf>,
+}
+
+fn is_zero(depth: &usize) -> bool {
+    *depth == 0
+}
+
+impl Folds {
+    // Whether the tree view shown from `root` has `dir` open
+    pub fn is_open(&self, root: &Path, dir: &Path) -> bool {
+        self
+        .roots
+        .get(root)
+        .is_some_and(|folds| folds.opens_by_default(root, dir) != folds.toggled.contains(dir))
+    }
+
+    pub fn open(&mut self, root: &Path, dir: &Path) {
+        self.set_open(root, dir, true);
+    }
+
+    pub fn close(&mut self, root: &Path, dir: &Path) {
+        self.set_open(root, dir, false);
+    }
+
+    fn set_open(&mut self, root: &Path, dir: &Path, open: bool) {
+        let folds = self.roots.entry(root.to_path_buf()).or_default();
+        if open == folds.opens_by_default(root, dir) {
+            folds.toggled.remove(dir);
+        } else {
+            folds.toggled.insert(dir.to_path_buf());
+        }
+    }
+
+    // Note that the tree view is shown from `root`
+    pub fn touch(&mut self, root: &Path) {
+        self.clock += 1;
+        self.roots.entry(root.to_path_buf()).or_default().used = self.clock;
+    }
+
+    // Open everything below `root` down to `depth` levels
+    pub fn expand_all(&mut self, root: &Path, depth: usize) {
+        let folds = self.roots.entry(root.to_path_buf()).or_default();
+        folds.depth = depth;
+        folds.toggled.clear();
+    }
+
+    // Fold everything below `root`
+    pub fn reset(&mut self, root: &Path) {
+        self.expand_all(root, 0);
+    }
+
+    // Fold the directories below `root` that are `depth` levels down or
+    // deeper, counting its own entries as level 1
+    pub fn fold_to_depth(&mut self, root: &Path, depth: usize) {
+        if let Some(folds) = self.roots.get_mut(root) {
+            folds.depth = folds.depth.min(depth);
+            // Deeper ones are all folded now, as they are by default
+            folds.toggled.retain(|dir| levels(root, dir) < depth);
+        }
+    }
+
+    // Where folds are kept between sessions: $XDG_STATE_HOME/greenmerald/
+    // folds.toml, or ~/.local/state/greenmerald/folds.toml
+    pub fn state_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+        Some(base.join("greenmerald").join("folds.toml"))
+    }
+
+    // The folds saved by the last session, leaving out directories removed
+    // since. A missing or unreadable file just means starting over.
+    pub fn load_saved() -> Folds {
+        let saved: SavedFolds = Folds::state_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default();
+        Folds::from_saved(saved)
+    }
+
+    fn from_saved(saved: SavedFolds) -> Folds {
+        let mut folds = Folds::default();
+        // Most recent first
+        for saved in saved.roots.into_iter().rev() {
+            if !saved.path.is_dir() {
+                continue;
+            }
+            folds.clock += 1;
+            let toggled = saved
+            .toggled
+            .iter()
+            .map(|dir| saved.path.join(dir))
+            .filter(|dir| dir.is_dir())
+            .collect();
+            let root = RootFolds {
+                depth: saved.depth,
+                toggled,
+                used: folds.clock,
+            };
+            folds.roots.insert(saved.path, root);
+        }
+        folds
+    }
+
+    // Write the folds out for the next session
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Folds::state_path() else {
+            return Ok(());
+        };
+        let text = toml::to_string(&self.to_saved()).map_err(io::Error::other)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    // The folds of the roots shown last that differ from the default, with
+    // directories relative to their root. Directories removed meanwhile
+    // are left out.
+    fn to_saved(&self) -> SavedFolds {
+        let mut roots: Vec<(&PathBuf, &RootFolds)> = self
+        .roots
+        .iter()
+        // Paths that aren't valid UTF-8 can't be written to TOML
+        .filter(|(root, _)| root.is_dir() && root.to_str().is_some())
+        .collect();
+        roots.sort_by_key(|(_, folds)| std::cmp::Reverse(folds.used));
+        let roots = roots
+        .into_iter()
+        .filter_map(|(root, folds)| {
+            let mut toggled: Vec<PathBuf> = folds
+            .toggled
+            .iter()
+            .filter(|dir| dir.is_dir() && dir.to_str().is_some())
+            .filter_map(|dir| dir.strip_prefix(root).ok().map(Path::to_path_buf))
+            .collect();
+            toggled.sort();
+            (folds.depth > 0 || !toggled.is_empty()).then(|| SavedRoot {
+                path: root.clone(),
+                depth: folds.depth,
+                toggled,
+            })
+        })
+        .take(MAX_SAVED_ROOTS)
+        .collect();
+        SavedFolds { roots }
+    }
+}
+
+impl App {
+    // Open every directory of the tree view, up to the `[tree]` limits
+    pub fn expand_all(&mut self) {
+        if self.in_tree_view() {
+            self.folds.expand_all(&self.current_path, self.config.tree.max_depth);
+            self.reload_tree();
+        }
+    }
+
+    // Fold the tree view back to the current directory's own entries
+    pub fn collapse_all(&mut self) {
+        if self.in_tree_view() {
+            self.folds.reset(&self.current_path);
+            self.reload_tree();
+        }
+    }
+
+    // Ask for the depth to fold the tree view to; the next key gives it
+    pub fn start_collapse_to_depth(&mut self) {
+        if self.in_tree_view() {
+            self.input_mode = InputMode::FoldDepth;
+            self.status_message = Some("Collapse to depth: 1-9".to_string());
+        }
+    }
+
+    // A digit folds everything that many levels down; anything else cancels
+    pub fn handle_fold_depth_key(&mut self, code: KeyCode) {
+        self.input_mode = InputMode::Normal;
+        if let KeyCode::Char(c) = code
+            && let Some(depth) = c.to_digit(10).filter(|depth| *depth > 0)
+        {
+            self.folds.fold_to_depth(&self.current_path, depth as usize);
+            self.reload_tree();
+            self.status_message = Some(format!("Collapsed to depth {depth}"));
+        }
+    }
+
+    pub fn save_folds(&self) -> io::Result<()> {
+        self.folds.save()
+    }
+
+    fn in_tree_view(&mut self) -> bool {
+        if !self.recursive_view {
+            self.status_message = Some("Only the tree view folds ('e' switches to it)".to_string());
+        }
+        self.recursive_view
+    }
+
+    // List the tree again after its folds changed, keeping the selected
+    // entry, or the folder it was folded into, selected
+    fn reload_tree(&mut self) {
+        let selected = self.get_selected_entry().map(|entry| entry.path());
+        let previewed = self.previewed_path();
+        self.load_current_entries(true);
+        if let Some(selected) = selected {
+            let index = selected
+            .ancestors()
+            .take_while(|path| *path != self.current_path)
+            .find_map(|path| self.entry_index(path));
+            if let Some(index) = index {
+                self.current_selected.select(Some(index));
+            }
+        }
+        if self.previewed_path() != previewed {
+            self.update_preview();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_against_the_depth_round_trips() {
+        let root = Path::new("/r");
+        let mut folds = Folds::default();
+        assert!(!folds.is_open(root, &root.join("a")));
+
+        folds.expand_all(root, 2);
+        assert!(folds.is_open(root, &root.join("a")));
+        assert!(!folds.is_open(root, &root.join("a/b")));
+
+        // Folding what is open by default is remembered, and opening it
+        // again forgets it
+        folds.close(root, &root.join("a"));
+        assert!(!folds.is_open(root, &root.join("a")));
+        folds.open(root, &root.join("a"));
+        assert!(folds.is_open(root, &root.join("a")));
+        // ...and the other way round below the depth
+        folds.open(root, &root.join("a/b"));
+        assert!(folds.is_open(root, &root.join("a/b")));
+        folds.close(root, &root.join("a/b"));
+        assert!(folds.roots[root].toggled.is_empty());
+
+        // Each root has its own folds
+        assert!(!folds.is_open(Path::new("/other"), Path::new("/other/a")));
+    }
+
+    #[test]
+    fn folding_to_a_depth_forgets_only_deeper_toggles() {
+        let root = Path::new("/r");
+        let mut folds = Folds::default();
+        folds.expand_all(root, 3);
+        folds.close(root, &root.join("a"));
+        folds.close(root, &root.join("x/y"));
+        folds.open(root, &root.join("x/y/z"));
+
+        folds.fold_to_depth(root, 2);
+        // `a` is above the new depth and stays folded by hand
+        assert_eq!(folds.roots[root].toggled, HashSet::from([root.join("a")]));
+        assert!(!folds.is_open(root, &root.join("a")));
+        assert!(folds.is_open(root, &root.join("x")));
+        assert!(!folds.is_open(root, &root.join("x/y")));
+        assert!(!folds.is_open(root, &root.join("x/y/z")));
+    }
+
+    #[test]
+    fn saved_folds_round_trip_through_toml() {
+        let base = env::temp_dir().join(format!("greenmerald-folds-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let roots: Vec<PathBuf> = (0..MAX_SAVED_ROOTS + 5).map(|i| base.join(format!("root{i}"))).collect();
+        for root in &roots {
+            fs::create_dir_all(root.join("sub/inner")).unwrap();
+        }
+        fs::create_dir_all(base.join("plain")).unwrap();
+
+        let mut folds = Folds::default();
+        for root in &roots {
+            folds.touch(root);
+            folds.expand_all(root, 1);
+        }
+        let last = roots.last().unwrap();
+        folds.open(last, &last.join("sub/inner"));
+        folds.open(last, &last.join("gone"));
+        // Nothing differs from the default here, so it is not saved
+        folds.touch(&base.join("plain"));
+
+        let text = toml::to_string(&folds.to_saved()).unwrap();
+        // Directories are written relative to their root
+        assert!(text.contains("\"sub/inner\""), "{text}");
+        assert!(!text.contains("gone"), "{text}");
+
+        let loaded = Folds::from_saved(toml::from_str(&text).unwrap());
+        // The roots shown last are the ones kept
+        assert_eq!(loaded.roots.len(), MAX_SAVED_ROOTS);
+        assert!(!loaded.roots.contains_key(&roots[4]));
+        assert!(loaded.roots.contains_key(&roots[5]));
+        assert!(!loaded.roots.contains_key(&base.join("plain")));
+        assert_eq!(loaded.roots[last].depth, 1);
+        assert_eq!(loaded.roots[last].toggled, HashSet::from([last.join("sub/inner")]));
+        assert!(loaded.roots[last].used > loaded.roots[&roots[5]].used);
+
+        // Roots removed since are dropped on loading
+        fs::remove_dir_all(last).unwrap();
+        let loaded = Folds::from_saved(toml::from_str(&text).unwrap());
+        assert_eq!(loaded.roots.len(), MAX_SAVED_ROOTS - 1);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,13 +1,17 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 // We bring in the App struct from the parent module (src/app/mod.rs or src/app/state.rs)
-use super::state::App;
+use super::editor::EditRequest;
+use super::keymap::{Action, KeyCombo, Lookup};
+use super::state::{App, InputMode};
 
 /// Defines the return type for event handling, allowing us to signal if the application
 /// should quit.
 pub enum HandlerResult {
     Continue,
     Quit,
+    /// Suspend the TUI and open a file in the user's editor.
+    Edit(EditRequest),
 }
 
 impl App {
@@ -17,45 +21,227 @@ impl App {
     /// into actionable state changes, aligning with the Command Pattern.
     pub fn handle_key_event(&mut self, key: KeyEvent) -> HandlerResult {
         let (code, modifiers) = (key.code, key.modifiers);
+        self.status_message = None;
 
-        match (code, modifiers) {
+        // Esc always abandons a half-typed key sequence
+        if code == KeyCode::Esc && !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            return HandlerResult::Continue;
+        }
+
+        match self.input_mode {
+            InputMode::PreviewSearch => {
+                self.handle_search_key(code, modifiers);
+                return HandlerResult::Continue;
+            }
+            InputMode::FileSearch => {
+                self.handle_file_search_key(code, modifiers);
+                return HandlerResult::Continue;
+            }
+            InputMode::Finder => {
+                self.handle_finder_key(code, modifiers);
+                return HandlerResult::Continue;
+            }
+            InputMode::Help => {
+                self.handle_help_key(key.into());
+                return HandlerResult::Continue;
+            }
+            InputMode::FoldDepth => {
+                self.handle_fold_depth_key(code);
+                return HandlerResult::Continue;
+            }
+            InputMode::Normal => {}
+        }
+
+        match self.resolve_key(key.into()) {
+            Some(action) => self.run_action(action),
+            None => HandlerResult::Continue,
+        }
+    }
+
+    /// Looks up a key in the keymap, remembering it if it starts a longer
+    /// sequence such as `gg`.
+    ///
+    /// A key that doesn't continue the pending sequence abandons it and is
+    /// then looked up on its own.
+    pub(super) fn resolve_key(&mut self, key: KeyCombo) -> Option<Action> {
+        self.pending_keys.push(key);
+        match self.keymap.lookup(&self.pending_keys) {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                Some(action)
+            }
+            Lookup::Prefix => None,
+            Lookup::None => {
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if retry { self.resolve_key(key) } else { None }
+            }
+        }
+    }
+
+    /// Carries out a bound action.
+    ///
+    /// What an action does depends on what is on screen: the pager has no
+    /// panels, so moving the selection scrolls the document, and search
+    /// results stand in for the Current panel, so the selection keys move
+    /// through the hits. A focused outline takes the selection keys for its
+    /// headings.
+    pub fn run_action(&mut self, action: Action) -> HandlerResult {
+        let pager = self.pager.is_some();
+        let results = self.file_search.is_some();
+        let outline = self.outline_focus.is_some();
+
+        match action {
             // --- Global Control ---
-            // Quit application on 'q'
-            (KeyCode::Char('q'), _) => return HandlerResult::Quit,
+            Action::Quit => return HandlerResult::Quit,
+            Action::ShowHelp => self.open_help(),
 
-            // --- Preview Panel Scrolling (Ctrl+j/k) ---
-            (KeyCode::Char('j'), KeyModifiers::CONTROL) => self.scroll_preview_down(),
-            (KeyCode::Char('k'), KeyModifiers::CONTROL) => self.scroll_preview_up(),
+            // --- Editing and Export ---
+            Action::Edit => {
+                if let Some(request) = self.edit_request() {
+                    return HandlerResult::Edit(request);
+                }
+            }
+            Action::Export => self.export_selected(),
 
             // --- Navigation & View Control ---
+            Action::SelectNext if outline => self.move_outline_focus(1),
+            Action::SelectPrevious if outline => self.move_outline_focus(-1),
+            Action::EnterDirectory if outline => self.open_focused_heading(),
+            Action::LeaveDirectory | Action::Cancel if outline => self.unfocus_outline(),
+            Action::SelectNext if pager => self.scroll_preview_by(1),
+            Action::SelectNext if results => self.select_next_hit(),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious if pager => self.scroll_preview_by(-1),
+            Action::SelectPrevious if results => self.select_previous_hit(),
+            Action::SelectPrevious => self.select_previous(),
+            // A focused link takes Enter before anything else
+            Action::EnterDirectory if self.selected_link.is_some() => self.follow_selected_link(),
+            Action::EnterDirectory if pager => self.scroll_preview_by(1),
+            Action::EnterDirectory if results => self.open_selected_hit(),
+            Action::EnterDirectory => self.enter_directory(),
+            Action::LeaveDirectory if pager => {}
+            Action::LeaveDirectory if results => self.close_file_search(),
+            Action::LeaveDirectory => self.leave_directory(),
+            Action::HistoryBack if !pager => self.go_back(),
+            Action::HistoryForward if !pager => self.go_forward(),
+            Action::ToggleViewMode if !pager => self.toggle_recursive_view(),
+            // Only available in recursive view
+            Action::ToggleFold if !pager && !results => self.toggle_fold(),
+            Action::ExpandAll if !pager && !results => self.expand_all(),
+            Action::CollapseAll if !pager && !results => self.collapse_all(),
+            Action::CollapseToDepth if !pager && !results => self.start_collapse_to_depth(),
+            Action::ToggleHidden if !pager => self.toggle_hidden(),
+            Action::ToggleIgnored if !pager => self.toggle_ignored(),
+            Action::CycleSort if !pager => self.cycle_sort(),
+            Action::ReverseSort if !pager => self.reverse_sort(),
+            Action::ToggleDirsFirst if !pager => self.toggle_dirs_first(),
 
-            // Toggle Recursive/Flat view on 'e'
-            (KeyCode::Char('e'), _) => self.toggle_recursive_view(),
+            // --- Preview Scrolling ---
+            Action::ScrollPreviewDown if pager => self.scroll_preview_by(1),
+            Action::ScrollPreviewDown => self.scroll_preview_down(),
+            Action::ScrollPreviewUp if pager => self.scroll_preview_by(-1),
+            Action::ScrollPreviewUp => self.scroll_preview_up(),
+            Action::PageDown => self.page_down(),
+            Action::PageUp => self.page_up(),
+            Action::HalfPageDown => self.half_page_down(),
+            Action::HalfPageUp => self.half_page_up(),
+            Action::ScrollToTop => self.scroll_to_top(),
+            Action::ScrollToBottom => self.scroll_to_bottom(),
 
-            // Toggle fold/unfold on 't' (only available in recursive view)
-            (KeyCode::Char('t'), _) => self.toggle_fold(),
+            // --- Links in the Preview ---
+            Action::NextLink => self.select_next_link(),
+            Action::PreviousLink => self.select_previous_link(),
+            // Clears a focused link first, then closes search results
+            Action::Cancel if results && self.selected_link.is_none() => self.close_file_search(),
+            Action::Cancel => {
+                self.clear_link_selection();
+                self.cancel_preview_search();
+            }
 
-            // Move selection down (j or Down arrow)
-            (KeyCode::Char('j'), _) | (KeyCode::Down, _) => self.select_next(),
+            // --- Search ---
+            Action::SearchPreview => self.start_preview_search(),
+            Action::NextMatch => self.next_search_match(),
+            Action::PreviousMatch => self.previous_search_match(),
+            Action::FindInFiles if !pager => self.start_file_search(),
+            // There is nothing to find in the pager; Ctrl+p scrolls up as in
+            // `less`
+            Action::OpenFinder if pager => self.scroll_preview_by(-1),
+            Action::OpenFinder => self.open_finder(),
 
-            // Move selection up (k or Up arrow)
-            (KeyCode::Char('k'), _) | (KeyCode::Up, _) => self.select_previous(),
+            // --- Outline ---
+            Action::ToggleOutline if !pager => self.toggle_outline(),
+            Action::FocusOutline if outline => self.unfocus_outline(),
+            Action::FocusOutline if !pager => self.focus_outline(),
+            Action::NextHeading => self.next_heading(),
+            Action::PreviousHeading => self.previous_heading(),
 
-            // Go up directory (h, Backspace, or Left arrow)
-            (KeyCode::Char('h'), _) | (KeyCode::Backspace, _) | (KeyCode::Left, _) => {
-                self.leave_directory()
-            }
+            // --- Appearance ---
+            Action::CycleTheme => self.cycle_theme(),
 
-            // Enter directory (l, Enter, or Right arrow)
-            (KeyCode::Char('l'), _) | (KeyCode::Enter, _) | (KeyCode::Right, _) => {
-                self.enter_directory()
-            }
+            // The browser's panels don't exist in the pager
+            _ => {}
+        }
+        HandlerResult::Continue
+    }
 
-            // Ignore all other keys
+    /// Edits the `/` query while search mode is active.
+    ///
+    /// Matches are recomputed on every change so the preview follows the
+    /// query as it is typed.
+    fn handle_search_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match (code, modifiers) {
+            (KeyCode::Enter, _) => self.confirm_preview_search(),
+            (KeyCode::Esc, _) => self.cancel_preview_search(),
+            (KeyCode::Backspace, _) => self.pop_search_char(),
+            // Toggle case sensitivity (Alt+c) and regex mode (Alt+r)
+            (KeyCode::Char('c'), KeyModifiers::ALT) => self.toggle_search_case(),
+            (KeyCode::Char('r'), KeyModifiers::ALT) => self.toggle_search_regex(),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.push_search_char(c),
             _ => {}
         }
+    }
 
-        // Default: continue the main loop
-        HandlerResult::Continue
+    /// Edits the query of a search across files before it is started.
+    fn handle_file_search_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match (code, modifiers) {
+            (KeyCode::Enter, _) => self.submit_file_search(),
+            (KeyCode::Esc, _) => self.close_file_search(),
+            (KeyCode::Backspace, _) => self.pop_file_search_char(),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.push_file_search_char(c),
+            _ => {}
+        }
+    }
+
+    /// Handles keys while the fuzzy finder popup is open.
+    ///
+    /// Typing narrows the candidates; the arrow keys (or Ctrl+n/Ctrl+p) move
+    /// through them and the preview follows the highlighted one.
+    fn handle_finder_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match (code, modifiers) {
+            (KeyCode::Enter, _) => self.open_selected_candidate(),
+            (KeyCode::Esc, _) => self.close_finder(),
+            (KeyCode::Backspace, _) => self.pop_finder_char(),
+            (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => self.select_next_candidate(),
+            (KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => self.select_previous_candidate(),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.push_finder_char(c),
+            _ => {}
+        }
+    }
+
+    /// Handles keys while the key help overlay is open: the keys bound to
+    /// moving the selection or scrolling the preview scroll it and anything
+    /// else closes it.
+    fn handle_help_key(&mut self, key: KeyCombo) {
+        match self.resolve_key(key) {
+            Some(Action::SelectNext | Action::ScrollPreviewDown) => self.help_scroll = self.help_scroll.saturating_add(1),
+            Some(Action::SelectPrevious | Action::ScrollPreviewUp) => self.help_scroll = self.help_scroll.saturating_sub(1),
+            Some(Action::ScrollToTop) => self.help_scroll = 0,
+            Some(Action::ScrollToBottom) => self.help_scroll = u16::MAX,
+            // Waiting for the rest of a sequence such as `gg`
+            None if !self.pending_keys.is_empty() => {}
+            _ => self.close_help(),
+        }
     }
 }
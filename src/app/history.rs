@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use super::state::App;
+
+// Oldest entries are dropped once the back stack grows past this
+const MAX_HISTORY: usize = 100;
+
+// A snapshot of what the user was looking at
+pub struct HistoryEntry {
+    pub path: PathBuf,
+    pub recursive_view: bool,
+    // Selected entry in the Current panel. When it is a Markdown file this
+    // is also the document that was open in the preview.
+    pub selected: Option<PathBuf>,
+    pub preview_scroll_offset: u16,
+}
+
+// Browser-style back/forward stacks
+#[derive(Default)]
+pub struct History {
+    back: Vec<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
+}
+
+impl App {
+    fn snapshot(&self) -> HistoryEntry {
+        HistoryEntry {
+            path: self.current_path.clone(),
+            recursive_view: self.recursive_view,
+            selected: self.get_selected_entry().map(|entry| entry.path()),
+            preview_scroll_offset: self.preview_scroll_offset,
+        }
+    }
+
+    // Record the current view before navigating away from it.
+    // A new navigation invalidates everything we could go forward to.
+    pub fn push_history(&mut self) {
+        let entry = self.snapshot();
+        self.history.back.push(entry);
+        if self.history.back.len() > MAX_HISTORY {
+            self.history.back.remove(0);
+        }
+        self.history.forward.clear();
+    }
+
+    // Return to the previous view
+    pub fn go_back(&mut self) {
+        if let Some(entry) = self.history.back.pop() {
+            let current = self.snapshot();
+            self.history.forward.push(current);
+            self.restore(entry);
+        } else {
+            self.status_message = Some("Already at the oldest history entry".to_string());
+        }
+    }
+
+    // Redo a navigation undone with `go_back`
+    pub fn go_forward(&mut self) {
+        if let Some(entry) = self.history.forward.pop() {
+            let current = self.snapshot();
+            self.history.back.push(current);
+            self.restore(entry);
+        } else {
+            self.status_message = Some("Already at the newest history entry".to_string());
+        }
+    }
+
+    fn restore(&mut self, entry: HistoryEntry) {
+        self.current_path = entry.path;
+        self.recursive_view = entry.recursive_view;
+        self.update_panels();
+        if let Some(selected) = &entry.selected {
+            self.select_entry_path(selected);
+        }
+        self.preview_scroll_offset = entry.preview_scroll_offset;
+        // Selecting it once it is listed shows the document from the top
+        // again, so scroll back then too
+        if let Some((_, scroll)) = &mut self.pending_selection {
+            *scroll = Some(entry.preview_scroll_offset);
+        }
+    }
+
+    // Select the entry with this path in the Current panel, if it is listed,
+    // or once the folder holding it is listed
+    pub fn select_entry_path(&mut self, path: &Path) -> bool {
+        match self
+        .current_entries
+        .iter()
+        .position(|tree_entry| tree_entry.entry.path() == path)
+        {
+            Some(index) => {
+                self.current_selected.select(Some(index));
+                self.update_preview();
+                true
+            }
+            None => {
+                // The recursive view may still be listing its folder
+                if self.is_loading_tree() && path.starts_with(&self.current_path) {
+                    self.pending_selection = Some((path.to_path_buf(), None));
+                }
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::{collections::BTreeMap, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::state::{App, InputMode};
+use crate::config::KeyList;
+
+// ## Key Bindings ##
+// Keys in the browser and the pager are looked up here and turned into
+// actions, which `App::run_action` carries out. Text entry (searches, the
+// finder) is not remappable.
+
+// Everything a key can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ShowHelp,
+    SelectNext,
+    SelectPrevious,
+    EnterDirectory,
+    LeaveDirectory,
+    HistoryBack,
+    HistoryForward,
+    ToggleViewMode,
+    ToggleFold,
+    ExpandAll,
+    CollapseAll,
+    CollapseToDepth,
+    ToggleHidden,
+    ToggleIgnored,
+    CycleSort,
+    ReverseSort,
+    ToggleDirsFirst,
+    ScrollPreviewDown,
+    ScrollPreviewUp,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    ScrollToTop,
+    ScrollToBottom,
+    NextLink,
+    PreviousLink,
+    SearchPreview,
+    NextMatch,
+    PreviousMatch,
+    FindInFiles,
+    OpenFinder,
+    ToggleOutline,
+    FocusOutline,
+    CycleTheme,
+    NextHeading,
+    PreviousHeading,
+    Edit,
+    Export,
+    Cancel,
+}
+
+// An action's name in the `[keys]` config section, what it does (for the
+// help overlay) and its default keys
+struct ActionInfo {
+    action: Action,
+    name: &'static str,
+    description: &'static str,
+    keys: &'static [&'static str],
+}
+
+// In the order the help overlay lists them
+const ACTIONS: &[ActionInfo] = &[
+    ActionInfo { action: Action::Quit, name: "quit", description: "Quit", keys: &["q"] },
+    ActionInfo { action: Action::ShowHelp, name: "show_help", description: "Show this help", keys: &["?"] },
+    ActionInfo { action: Action::SelectNext, name: "select_next", description: "Move the selection down (pager: scroll down)", keys: &["j", "down"] },
+    ActionInfo { action: Action::SelectPrevious, name: "select_previous", description: "Move the selection up (pager: scroll up)", keys: &["k", "up"] },
+    ActionInfo { action: Action::EnterDirectory, name: "enter_directory", description: "Enter the directory, open the search hit or follow the focused link (pager: scroll down)", keys: &["l", "enter", "right"] },
+    ActionInfo { action: Action::LeaveDirectory, name: "leave_directory", description: "Go to the parent directory, or close search results", keys: &["h", "backspace", "left"] },
+    ActionInfo { action: Action::HistoryBack, name: "history_back", description: "Back to the previous directory or document", keys: &["H", "alt+left"] },
+    ActionInfo { action: Action::HistoryForward, name: "history_forward", description: "Forward again after going back", keys: &["L", "alt+right"] },
+    ActionInfo { action: Action::ToggleViewMode, name: "toggle_view_mode", description: "Switch between the flat and tree views", keys: &["e"] },
+    ActionInfo { action: Action::ToggleFold, name: "toggle_fold", description: "Fold or unfold the selected directory (tree view)", keys: &["t"] },
+    ActionInfo { action: Action::ExpandAll, name: "expand_all", description: "Open every directory of the tree view", keys: &["zR"] },
+    ActionInfo { action: Action::CollapseAll, name: "collapse_all", description: "Fold every directory of the tree view", keys: &["zM"] },
+    ActionInfo { action: Action::CollapseToDepth, name: "collapse_to_depth", description: "Fold the tree view to the depth typed next (1-9)", keys: &["zd"] },
+    ActionInfo { action: Action::ToggleHidden, name: "toggle_hidden", description: "Show or hide files whose name starts with a dot", keys: &["."] },
+    ActionInfo { action: Action::ToggleIgnored, name: "toggle_ignored", description: "Show or hide files .gitignore and .ignore leave out", keys: &["I"] },
+    ActionInfo { action: Action::CycleSort, name: "cycle_sort", description: "Sort by natural name, name, time modified, size or extension", keys: &["s"] },
+    ActionInfo { action: Action::ReverseSort, name: "reverse_sort", description: "Switch between ascending and descending order", keys: &["S"] },
+    ActionInfo { action: Action::ToggleDirsFirst, name: "toggle_dirs_first", description: "List directories first, or mixed in with files", keys: &["D"] },
+    ActionInfo { action: Action::ScrollPreviewDown, name: "scroll_preview_down", description: "Scroll the preview down a line", keys: &["ctrl+j", "ctrl+e", "ctrl+n"] },
+    ActionInfo { action: Action::ScrollPreviewUp, name: "scroll_preview_up", description: "Scroll the preview up a line", keys: &["ctrl+k", "ctrl+y"] },
+    ActionInfo { action: Action::PageDown, name: "page_down", description: "Scroll the preview down a page", keys: &["space", "f", "pgdn", "ctrl+f"] },
+    ActionInfo { action: Action::PageUp, name: "page_up", description: "Scroll the preview up a page", keys: &["b", "pgup", "ctrl+b"] },
+    ActionInfo { action: Action::HalfPageDown, name: "half_page_down", description: "Scroll the preview down half a page", keys: &["d", "ctrl+d"] },
+    ActionInfo { action: Action::HalfPageUp, name: "half_page_up", description: "Scroll the preview up half a page", keys: &["u", "ctrl+u"] },
+    ActionInfo { action: Action::ScrollToTop, name: "scroll_to_top", description: "Jump to the top of the preview", keys: &["gg", "home"] },
+    ActionInfo { action: Action::ScrollToBottom, name: "scroll_to_bottom", description: "Jump to the bottom of the preview", keys: &["G", "end"] },
+    ActionInfo { action: Action::NextLink, name: "next_link", description: "Focus the next link in the preview", keys: &["tab"] },
+    ActionInfo { action: Action::PreviousLink, name: "previous_link", description: "Focus the previous link in the preview", keys: &["shift+tab"] },
+    ActionInfo { action: Action::SearchPreview, name: "search_preview", description: "Search the preview", keys: &["/"] },
+    ActionInfo { action: Action::NextMatch, name: "next_match", description: "Jump to the next search match", keys: &["n"] },
+    ActionInfo { action: Action::PreviousMatch, name: "previous_match", description: "Jump to the previous search match", keys: &["N"] },
+    ActionInfo { action: Action::FindInFiles, name: "find_in_files", description: "Search every Markdown file under the current directory", keys: &["F"] },
+    ActionInfo { action: Action::OpenFinder, name: "open_finder", description: "Fuzzy-find a file below the current directory (pager: scroll up)", keys: &["ctrl+p"] },
+    ActionInfo { action: Action::ToggleOutline, name: "toggle_outline", description: "Show or hide the outline", keys: &["o"] },
+    ActionInfo { action: Action::FocusOutline, name: "focus_outline", description: "Move through the outline's headings with the selection keys; Enter scrolls to one", keys: &["O"] },
+    ActionInfo { action: Action::CycleTheme, name: "cycle_theme", description: "Switch to the next colour theme", keys: &["T"] },
+    ActionInfo { action: Action::NextHeading, name: "next_heading", description: "Scroll to the next heading", keys: &["]"] },
+    ActionInfo { action: Action::PreviousHeading, name: "previous_heading", description: "Scroll to the previous heading", keys: &["["] },
+    ActionInfo { action: Action::Edit, name: "edit", description: "Open the previewed file in $VISUAL/$EDITOR", keys: &["E"] },
+    ActionInfo { action: Action::Export, name: "export", description: "Export the previewed file or selected directory to HTML", keys: &["x"] },
+    ActionInfo { action: Action::Cancel, name: "cancel", description: "Clear the focused link and search, or close search results", keys: &["esc"] },
+];
+
+// One key press, e.g. Ctrl+j
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyCombo {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyCombo {
+        // Shift is already part of the character ('G') and of BackTab, and
+        // terminals disagree on whether they report it
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyCombo { code, modifiers }
+    }
+
+    // Parse one key as written in the config: "j", "G", "ctrl+j",
+    // "alt+left", "shift+tab", "space"... The Emacs spellings "C-n",
+    // "M-x" and "S-Tab" work too.
+    fn parse(spec: &str) -> Result<KeyCombo, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        while let Some((prefix, key)) = rest.split_at_checked(2)
+            && !key.is_empty()
+        {
+            modifiers |= match prefix {
+                "C-" => KeyModifiers::CONTROL,
+                "M-" | "A-" => KeyModifiers::ALT,
+                "S-" => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            rest = key;
+        }
+
+        // The key is after the last '+', unless the key is '+' itself
+        let (modifier_names, key) = match rest.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => match rest.rsplit_once('+') {
+                Some((modifiers, key)) if !key.is_empty() => (modifiers, key),
+                _ => ("", rest),
+            },
+        };
+
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{name}' in '{spec}'")),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pgup" | "pageup" => KeyCode::PageUp,
+                "pgdn" | "pagedown" => KeyCode::PageDown,
+                "insert" => KeyCode::Insert,
+                "delete" | "del" => KeyCode::Delete,
+                name if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse().ok()) => {
+                    KeyCode::F(n)
+                }
+                _ => return Err(format!("unknown key '{key}' in '{spec}'")),
+            },
+        };
+        Ok(KeyCombo::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyCombo {
+    fn from(key: KeyEvent) -> Self {
+        KeyCombo::new(key.code, key.modifiers)
+    }
+}
+
+// Written the way the config spells it
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::BackTab => write!(f, "shift+tab"),
+            KeyCode::PageUp => write!(f, "pgup"),
+            KeyCode::PageDown => write!(f, "pgdn"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => write!(f, "{}", format!("{code:?}").to_ascii_lowercase()),
+        }
+    }
+}
+
+// Parse a binding, which may be a sequence of keys separated by spaces
+// ("g g", "ctrl+x ctrl+e"). A run of plain characters like "gg" is a
+// sequence too.
+fn parse_sequence(spec: &str) -> Result<Vec<KeyCombo>, String> {
+    let mut sequence = Vec::new();
+    for token in spec.split_whitespace() {
+        let is_plain_run = token.chars().count() > 1
+            && !token.contains('+')
+            && token.chars().all(|c| c.is_ascii_punctuation() || c.is_alphanumeric())
+            && KeyCombo::parse(token).is_err();
+        if is_plain_run {
+            sequence.extend(token.chars().map(|c| KeyCombo::new(KeyCode::Char(c), KeyModifiers::NONE)));
+        } else {
+            sequence.push(KeyCombo::parse(token)?);
+        }
+    }
+    if sequence.is_empty() {
+        return Err("empty key binding".to_string());
+    }
+    Ok(sequence)
+}
+
+fn format_sequence(sequence: &[KeyCombo]) -> String {
+    let keys: Vec<String> = sequence.iter().map(KeyCombo::to_string).collect();
+    // "gg" rather than "g g" when every key is a single character
+    if keys.iter().all(|key| key.chars().count() == 1) {
+        keys.concat()
+    } else {
+        keys.join(" ")
+    }
+}
+
+// Whether one sequence would stop the other from ever being completed
+fn overlaps(a: &[KeyCombo], b: &[KeyCombo]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+// What the keys pressed so far amount to
+pub enum Lookup {
+    Action(Action),
+    // The start of a longer binding; wait for the next key
+    Prefix,
+    None,
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyCombo>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&BTreeMap::new()).expect("the default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    // The default bindings with the `[keys]` config section applied. Each
+    // action listed there gets exactly the keys given (none for `[]`), and
+    // those keys are taken away from any other action.
+    pub fn new(overrides: &BTreeMap<String, KeyList>) -> Result<Keymap, String> {
+        let mut custom: Vec<(Vec<KeyCombo>, Action)> = Vec::new();
+        for (name, keys) in overrides {
+            let Some(info) = ACTIONS.iter().find(|info| info.name == name) else {
+                return Err(format!("unknown action '{name}' (press '?' in the app for the list)"));
+            };
+            for spec in keys.specs() {
+                let sequence = parse_sequence(spec).map_err(|e| format!("{name}: {e}"))?;
+                if let Some((_, other)) = custom.iter().find(|(keys, _)| overlaps(keys, &sequence)) {
+                    let other = ACTIONS.iter().find(|info| info.action == *other).map_or("", |info| info.name);
+                    return Err(format!("{name}: '{spec}' clashes with a key bound to {other}"));
+                }
+                custom.push((sequence, info.action));
+            }
+        }
+
+        let mut bindings = custom.clone();
+        for info in ACTIONS.iter().filter(|info| !overrides.contains_key(info.name)) {
+            for spec in info.keys {
+                let sequence = parse_sequence(spec).map_err(|e| format!("{}: {e}", info.name))?;
+                if !custom.iter().any(|(keys, _)| overlaps(keys, &sequence)) {
+                    bindings.push((sequence, info.action));
+                }
+            }
+        }
+        Ok(Keymap { bindings })
+    }
+
+    pub fn lookup(&self, keys: &[KeyCombo]) -> Lookup {
+        let mut prefix = false;
+        for (sequence, action) in &self.bindings {
+            if sequence == keys {
+                return Lookup::Action(*action);
+            }
+            prefix |= sequence.starts_with(keys);
+        }
+        if prefix { Lookup::Prefix } else { Lookup::None }
+    }
+
+    // (keys, action name, description) for every action, for the help
+    // overlay. Unbound actions are listed with no keys.
+    pub fn help(&self) -> Vec<(String, &'static str, &'static str)> {
+        ACTIONS
+        .iter()
+        .map(|info| {
+            let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, action)| *action == info.action)
+            .map(|(sequence, _)| format_sequence(sequence))
+            .collect();
+            (keys.join(", "), info.name, info.description)
+        })
+        .collect()
+    }
+}
+
+// Shown in the header while a sequence is half typed, e.g. "g"
+pub fn pending_text(keys: &[KeyCombo]) -> String {
+    format_sequence(keys)
+}
+
+impl App {
+    // Open the overlay listing every action and its keys
+    pub fn open_help(&mut self) {
+        self.help_scroll = 0;
+        self.input_mode = InputMode::Help;
+    }
+
+    pub fn close_help(&mut self) {
+        self.input_mode = InputMode::Normal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyCombo {
+        KeyCombo::new(code, modifiers)
+    }
+
+    fn char(c: char) -> KeyCombo {
+        key(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn keys(bindings: &[(&str, &[&str])]) -> BTreeMap<String, KeyList> {
+        bindings
+        .iter()
+        .map(|(name, keys)| (name.to_string(), KeyList::Many(keys.iter().map(|key| key.to_string()).collect())))
+        .collect()
+    }
+
+    #[test]
+    fn parses_chords_in_either_spelling() {
+        let ctrl_n = key(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(parse_sequence("C-n"), Ok(vec![ctrl_n]));
+        assert_eq!(parse_sequence("ctrl+n"), Ok(vec![ctrl_n]));
+        assert_eq!(parse_sequence("S-Tab"), Ok(vec![key(KeyCode::BackTab, KeyModifiers::NONE)]));
+        assert_eq!(parse_sequence("shift+tab"), Ok(vec![key(KeyCode::BackTab, KeyModifiers::NONE)]));
+        assert_eq!(parse_sequence("M-left"), Ok(vec![key(KeyCode::Left, KeyModifiers::ALT)]));
+        assert_eq!(parse_sequence("ctrl++"), Ok(vec![key(KeyCode::Char('+'), KeyModifiers::CONTROL)]));
+    }
+
+    #[test]
+    fn parses_runs_of_characters_as_sequences() {
+        assert_eq!(parse_sequence("gg"), Ok(vec![char('g'), char('g')]));
+        assert_eq!(parse_sequence("zR"), Ok(vec![char('z'), char('R')]));
+        assert_eq!(
+            parse_sequence("ctrl+x e"),
+            Ok(vec![key(KeyCode::Char('x'), KeyModifiers::CONTROL), char('e')])
+        );
+        assert_eq!(format_sequence(&parse_sequence("zR").unwrap()), "zR");
+    }
+
+    #[test]
+    fn rejects_bad_names() {
+        assert!(parse_sequence("hyper+x").is_err());
+        assert!(parse_sequence("ctrl+nosuchkey").is_err());
+        assert!(parse_sequence("").is_err());
+        assert!(Keymap::new(&keys(&[("no_such_action", &["x"])])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_key_that_starts_another_binding() {
+        let error = Keymap::new(&keys(&[("quit", &["g"]), ("scroll_to_top", &["gg"])])).err().unwrap();
+        assert!(error.contains("clashes"), "{error}");
+        assert!(Keymap::new(&keys(&[("quit", &["x", "x"])])).is_err());
+    }
+
+    #[test]
+    fn custom_keys_take_over_from_the_defaults() {
+        // 'j' moves to quit, so select_next keeps only its other default
+        let keymap = Keymap::new(&keys(&[("quit", &["j"])])).unwrap();
+        assert!(matches!(keymap.lookup(&[char('j')]), Lookup::Action(Action::Quit)));
+        assert!(matches!(keymap.lookup(&[char('q')]), Lookup::None));
+        let down = key(KeyCode::Down, KeyModifiers::NONE);
+        assert!(matches!(keymap.lookup(&[down]), Lookup::Action(Action::SelectNext)));
+        // A default sequence starting with a taken key is dropped
+        let keymap = Keymap::new(&keys(&[("quit", &["g"])])).unwrap();
+        assert!(matches!(keymap.lookup(&[char('g')]), Lookup::Action(Action::Quit)));
+    }
+
+    #[test]
+    fn a_key_that_breaks_a_sequence_is_looked_up_alone() {
+        let mut app = App::default();
+        assert_eq!(app.resolve_key(char('g')), None);
+        assert_eq!(app.pending_keys, [char('g')]);
+        assert_eq!(app.resolve_key(char('g')), Some(Action::ScrollToTop));
+        assert!(app.pending_keys.is_empty());
+
+        // 'j' doesn't continue "g...", so it moves the selection instead
+        assert_eq!(app.resolve_key(char('g')), None);
+        assert_eq!(app.resolve_key(char('j')), Some(Action::SelectNext));
+        assert!(app.pending_keys.is_empty());
+
+        // Nor does an unbound key, which is dropped along with the prefix
+        assert_eq!(app.resolve_key(char('z')), None);
+        assert_eq!(app.resolve_key(char('!')), None);
+        assert!(app.pending_keys.is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use super::state::{App, PreviewState};
+
+// Default command used to open external links when $GREENMERALD_OPENER is unset
+#[cfg(target_os = "macos")]
+const DEFAULT_OPENER: &str = "open";
+#[cfg(windows)]
+const DEFAULT_OPENER: &str = "explorer";
+#[cfg(not(any(target_os = "macos", windows)))]
+const DEFAULT_OPENER: &str = "xdg-open";
+
+// The command external URLs are handed to. May include arguments,
+// e.g. "firefox --new-tab".
+pub fn default_link_opener() -> String {
+    env::var("GREENMERALD_OPENER").unwrap_or_else(|_| DEFAULT_OPENER.to_string())
+}
+
+// Openers started for external links. A thread waits on each one so it
+// doesn't linger as a zombie, and reports back if it failed.
+pub struct Openers {
+    sender: Sender<String>,
+    receiver: Receiver<String>,
+}
+
+impl Default for Openers {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Openers { sender, receiver }
+    }
+}
+
+impl App {
+    // Number of links in the previewed Markdown document
+    fn link_count(&self) -> usize {
+        match &self.preview_content {
+            PreviewState::Markdown(doc) => doc.links().len(),
+            _ => 0,
+        }
+    }
+
+    // Focus the next link in the preview (Tab), wrapping around
+    pub fn select_next_link(&mut self) {
+        let count = self.link_count();
+        if count == 0 {
+            return;
+        }
+        let next = self.selected_link.map_or(0, |i| (i + 1) % count);
+        self.focus_link(next);
+    }
+
+    // Focus the previous link in the preview (Shift-Tab), wrapping around
+    pub fn select_previous_link(&mut self) {
+        let count = self.link_count();
+        if count == 0 {
+            return;
+        }
+        let previous = self.selected_link.map_or(count - 1, |i| (i + count - 1) % count);
+        self.focus_link(previous);
+    }
+
+    pub fn clear_link_selection(&mut self) {
+        self.selected_link = None;
+    }
+
+    fn focus_link(&mut self, index: usize) {
+        self.selected_link = Some(index);
+        if let PreviewState::Markdown(doc) = &self.preview_content {
+            let line = doc.links()[index].line;
+            self.scroll_preview_to_line(line);
+        }
+    }
+
+    // Act on the focused link: open Markdown files in the preview, jump to
+    // `#anchor` headings and hand anything else to the external opener
+    pub fn follow_selected_link(&mut self) {
+        let target = match (&self.preview_content, self.selected_link) {
+            (PreviewState::Markdown(doc), Some(i)) => match doc.links().get(i) {
+                Some(link) => link.target.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+
+        // Same-document anchor
+        if let Some(anchor) = target.strip_prefix('#') {
+            self.push_history();
+            self.jump_to_anchor(anchor);
+            return;
+        }
+
+        if is_external(&target) {
+            self.open_external(&target);
+            return;
+        }
+
+        // Relative path, optionally with an anchor: "guide.md#install"
+        let (path, anchor) = match target.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (target.as_str(), None),
+        };
+        let base = match self.previewed_path() {
+            Some(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => self.current_path.clone(),
+        };
+        let resolved = resolve_link_path(&base, &percent_decode(path));
+        if resolved.exists() && (resolved.is_dir() || self.is_markdown_path(&resolved)) {
+            // Browsing to the target leaves any search results behind
+            self.file_search = None;
+        }
+
+        if self.pager.is_some() {
+            // The pager shows one document at a time and has no panels to
+            // browse directories in
+            if resolved.is_file() && self.is_markdown_path(&resolved) {
+                self.open_in_pager(&resolved);
+                if let Some(anchor) = anchor {
+                    self.jump_to_anchor(anchor);
+                }
+            } else if resolved.exists() {
+                self.open_external(&resolved.to_string_lossy());
+            } else {
+                self.status_message = Some(format!("Link target not found: {}", resolved.display()));
+            }
+        } else if resolved.is_dir() {
+            self.push_history();
+            self.current_path = resolved;
+            self.recursive_view = false;
+            self.current_selected.select(Some(0));
+            self.update_panels();
+        } else if resolved.is_file() && self.is_markdown_path(&resolved) {
+            self.push_history();
+            self.select_path(&resolved);
+            if let Some(anchor) = anchor {
+                self.jump_to_anchor(anchor);
+            }
+        } else if resolved.exists() {
+            self.open_external(&resolved.to_string_lossy());
+        } else {
+            self.status_message = Some(format!("Link target not found: {}", resolved.display()));
+        }
+    }
+
+    // Scroll the preview so the heading with this anchor is at the top
+    pub fn jump_to_anchor(&mut self, anchor: &str) {
+        let line = match &self.preview_content {
+            PreviewState::Markdown(doc) => doc.anchor_line(&percent_decode(anchor)),
+            _ => None,
+        };
+        match line {
+            Some(line) => {
+                self.selected_link = None;
+                self.preview_scroll_offset = line.min(u16::MAX as usize) as u16;
+            }
+            None => self.status_message = Some(format!("No heading for #{anchor}")),
+        }
+    }
+
+    // Launch the configured opener on a URL or file, detached from the TUI
+    fn open_external(&mut self, target: &str) {
+        let mut parts = self.link_opener.split_whitespace();
+        let Some(program) = parts.next() else {
+            self.status_message = Some("No link opener configured".to_string());
+            return;
+        };
+        let result = Command::new(program)
+        .args(parts)
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+        let mut child = match result {
+            Ok(child) => child,
+            Err(e) => {
+                self.status_message = Some(format!("Could not run {program}: {e}"));
+                return;
+            }
+        };
+        self.status_message = Some(format!("Opened {target}"));
+        let sender = self.openers.sender.clone();
+        let program = program.to_string();
+        thread::spawn(move || {
+            let failure = match child.wait() {
+                Ok(status) if status.success() => return,
+                Ok(status) => format!("{program} failed ({status})"),
+                Err(e) => format!("{program} failed: {e}"),
+            };
+            // The receiver only goes away when the app exits
+            let _ = sender.send(failure);
+        });
+    }
+
+    // Show a failure of an opener that has exited since the last call.
+    // Returns true if there was one.
+    pub fn poll_openers(&mut self) -> bool {
+        let Some(failure) = self.openers.receiver.try_iter().last() else {
+            return false;
+        };
+        self.status_message = Some(failure);
+        true
+    }
+}
+
+fn is_external(target: &str) -> bool {
+    target.contains("://") || target.starts_with("mailto:")
+}
+
+// Where a link points, with `.` and `..` worked out so the path matches
+// the ones listings give. A target that exists has the directory holding it
+// canonicalized, keeping its own name so a link to a symlink selects the
+// symlink; one that doesn't is tidied up as written.
+fn resolve_link_path(base: &Path, link: &str) -> PathBuf {
+    let path = Path::new(link);
+    let path = if path.is_absolute() { path.to_path_buf() } else { base.join(path) };
+    if path.exists() {
+        let canonical = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+            // Ends in `..` (or is the root)
+            _ => fs::canonicalize(&path),
+        };
+        if let Ok(canonical) = canonical {
+            return canonical;
+        }
+    }
+    normalize_path(&path)
+}
+
+// Drop `.` components and let `..` take away the one before it, without
+// looking at the disk
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            // Nothing goes above the root
+            Component::ParentDir if normalized.has_root() => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// Decodes %XX escapes (e.g. "%20") in link targets
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_links_resolve_to_the_listed_path() {
+        let root = env::temp_dir().join(format!("greenmerald-links-{}", std::process::id()));
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/other.md"), "").unwrap();
+        let root = fs::canonicalize(&root).unwrap();
+        let base = root.join("a/b");
+
+        assert_eq!(resolve_link_path(&base, "../other.md"), root.join("a/other.md"));
+        assert_eq!(resolve_link_path(&base, "./../other.md"), root.join("a/other.md"));
+        assert_eq!(resolve_link_path(&base, ".."), root.join("a"));
+        assert_eq!(resolve_link_path(&base, "../.."), root.clone());
+        // Missing targets are tidied up without the disk
+        assert_eq!(resolve_link_path(&base, "../gone/../missing.md"), root.join("a/missing.md"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn normalizing_stops_at_the_root() {
+        assert_eq!(normalize_path(Path::new("/a/b/../../../c")), PathBuf::from("/c"));
+        assert_eq!(normalize_path(Path::new("a/./b/..")), PathBuf::from("a"));
+        assert_eq!(normalize_path(Path::new("../a")), PathBuf::from("../a"));
+    }
+}
@@ -1,84 +1,228 @@
 use std::{
+    collections::HashMap,
     env, fs,
-    path::PathBuf,
-    ffi::OsStr,
+    path::{Path, PathBuf},
 };
-use ratatui::widgets::ListState;
-use state::{App, TreeEntry, PreviewState};
-use crate::fs::{build_recursive_tree, get_entry_info, read_dir_entries};
+use ratatui::{layout::Rect, widgets::ListState};
+use state::{App, InputMode, PagerSource, StartOptions, PreviewState};
+use folds::Folds;
+use history::History;
+use links::Openers;
+use tree::Splice;
+use crate::config::Config;
+use crate::fs::watch::DirWatcher;
+use crate::fs::{ListOptions, get_entry_info, get_path_info, is_dir_entry, is_markdown_file, read_dir_entries};
+use crate::ui::markdown::MarkdownDocument;
+use crate::ui::theme::Theme;
 
 pub mod state;
 pub mod handler;
+pub mod editor;
+pub mod export;
+pub mod history;
+pub mod keymap;
+pub mod links;
+pub mod mouse;
+pub mod outline;
+pub mod pager;
+pub mod file_search;
+pub mod finder;
+pub mod folds;
+pub mod search;
+pub mod theme;
+pub mod tree;
+pub mod watch;
+
+
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-
+impl Default for StartOptions {
+    fn default() -> Self {
+        StartOptions {
+            path: env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+            select: None,
+            recursive: false,
+            list_options: ListOptions::default(),
+            theme: Theme::default(),
+            pager: None,
+            stdin: None,
+            config: Config::default(),
+        }
+    }
+}
 
 impl App {
-    // Create a new application instance
+    // Create a new application instance in the working directory
     pub fn new() -> App {
+        App::with_options(StartOptions::default())
+    }
+
+    // Create an application instance as configured on the command line
+    pub fn with_options(options: StartOptions) -> App {
         let mut app = App {
             should_quit: false,
-            recursive_view: false,
-            current_path: env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+            recursive_view: options.recursive,
+            current_path: options.path,
             current_entries: Vec::new(),
             current_selected: ListState::default(),
             parent_entries: Vec::new(),
             parent_selected: ListState::default(),
             preview_content: PreviewState::None,
             preview_scroll_offset: 0, // CHANGED
+            preview_modified: None,
+            theme: options.theme,
+            selected_link: None,
+            link_opener: links::default_link_opener(),
+            openers: Openers::default(),
+            status_message: None,
+            preview_area: Rect::default(),
+            parent_area: Rect::default(),
+            current_area: Rect::default(),
+            outline_area: Rect::default(),
+            outline_offset: 0,
+            outline_focus: None,
+            last_click: None,
+            history: History::default(),
+            show_outline: false,
+            input_mode: InputMode::Normal,
+            preview_search: None,
+            file_search: None,
+            finder: None,
+            exporting: None,
+            watcher: DirWatcher::new().ok(),
+            list_options: options.list_options,
+            pager: None,
+            // Already checked when the config was loaded
+            keymap: keymap::Keymap::new(&options.config.keys).unwrap_or_default(),
+            pending_keys: Vec::new(),
+            help_scroll: 0,
+            tree_loader: None,
+            tree_loading: HashMap::new(),
+            pending_selection: None,
+            folds: if options.config.tree.remember_folds { Folds::load_saved() } else { Folds::default() },
+            config: options.config,
         };
         app.current_selected.select(Some(0));
         app.update_panels(); // This will call update_preview() for us
+        if let Some(path) = &options.select {
+            app.select_entry_path(path);
+        }
+        if let Some(source) = options.stdin {
+            app.open_stdin_in_pager(source);
+        } else if let Some(path) = &options.pager {
+            app.open_in_pager(path);
+        }
         app
     }
 
 
     // Update the contents of the parent and current panels
     pub fn update_panels(&mut self) {
-        // -- Update current entries --
-        self.current_entries.clear(); // Clear the old list
+        self.load_panels();
+        self.update_preview();
+    }
 
-        if self.recursive_view {
-            // Build the full recursive tree
-            let _ = build_recursive_tree(&self.current_path, 0, &mut self.current_entries);
-        } else {
-            // Original flat view logic
-            if let Ok(entries) = read_dir_entries(&self.current_path) {
-                for entry in entries {
-                    self.current_entries.push(TreeEntry { entry, depth: 0 });
-                }
-            }
-        }
+    // Re-read the parent and current directories, leaving the preview alone
+    pub fn load_panels(&mut self) {
+        self.load_current_entries(false);
+        self.load_parent_entries();
+    }
 
-        if self.current_entries.is_empty() {
-            self.current_selected.select(None);
-        } else {
-            // Select 0, or clamp to new max
-            let new_max = self.current_entries.len() - 1;
-            if let Some(selected) = self.current_selected.selected() {
-                if selected > new_max {
-                    self.current_selected.select(Some(new_max));
-                }
-            } else {
-                self.current_selected.select(Some(0));
-            }
+    // List the current directory. The recursive view has the directories
+    // open in it listed in the background; with `refresh` they keep the
+    // entries they showed until then.
+    pub fn load_current_entries(&mut self, refresh: bool) {
+        // Whatever is still being listed belongs to the old listing
+        self.stop_loading_tree();
+        if self.recursive_view {
+            self.folds.touch(&self.current_path);
         }
+        let old = std::mem::take(&mut self.current_entries);
+        let old = if refresh && self.recursive_view { old } else { Vec::new() };
+
+        let listed = read_dir_entries(&self.current_path, &self.list_options).unwrap_or_default();
+        let mut splice = Splice::new(self, HashMap::new(), listed.len());
+        splice.push_listing(listed, 0, old);
+        let spliced = splice.finish();
+        self.apply_splice(spliced);
+        // Select 0, or clamp to new max
+        self.clamp_selection();
+    }
 
-        // -- Update parent entries (unchanged) --
+    // Re-read the parent directory
+    fn load_parent_entries(&mut self) {
         if let Some(parent_path) = self.current_path.parent() {
-            self.parent_entries = read_dir_entries(parent_path).unwrap_or_default();
+            self.parent_entries = read_dir_entries(parent_path, &self.list_options).unwrap_or_default();
         } else {
             self.parent_entries.clear();
         }
-        self.parent_selected.select(Some(0));
-        self.update_preview();
+        // Select the directory we are in, so the list scrolls to show it
+        let current = self
+        .parent_entries
+        .iter()
+        .position(|entry| entry.path() == self.current_path);
+        self.parent_selected.select(Some(current.unwrap_or(0)));
     }
 
     // -- Event Handlers --
 
     // NEW: Toggle the recursive view
     pub fn toggle_recursive_view(&mut self) {
+        let selected = self.get_selected_entry().map(|entry| entry.path());
         self.recursive_view = !self.recursive_view;
         self.update_panels();
+        // Keep the same entry selected, or in the flat view the folder it
+        // is in. The tree view selects it once its folder is listed.
+        if let Some(selected) = selected
+            && !self.select_entry_path(&selected)
+            && let Some(top) = selected.ancestors().find(|path| path.parent() == Some(&self.current_path))
+        {
+            self.select_entry_path(top);
+        }
+    }
+
+    // Show or hide dotfiles, keeping the selection if it is still listed
+    pub fn toggle_hidden(&mut self) {
+        self.list_options.show_hidden = !self.list_options.show_hidden;
+        self.refresh_panels();
+        let state = if self.list_options.show_hidden { "shown" } else { "hidden" };
+        self.status_message = Some(format!("Hidden files {state}"));
+    }
+
+    // Show or hide what .gitignore / .ignore rules leave out
+    pub fn toggle_ignored(&mut self) {
+        self.list_options.show_ignored = !self.list_options.show_ignored;
+        self.refresh_panels();
+        let state = if self.list_options.show_ignored { "shown" } else { "hidden" };
+        self.status_message = Some(format!("Ignored files {state}"));
+    }
+
+    // Switch to the next sort mode: natural, name, modified, size, extension
+    pub fn cycle_sort(&mut self) {
+        self.list_options.sort.by = self.list_options.sort.by.next();
+        self.resort();
+    }
+
+    // Flip between ascending and descending order
+    pub fn reverse_sort(&mut self) {
+        self.list_options.sort.reverse = !self.list_options.sort.reverse;
+        self.resort();
+    }
+
+    // List directories above files, or mixed in with them
+    pub fn toggle_dirs_first(&mut self) {
+        self.list_options.sort.dirs_first = !self.list_options.sort.dirs_first;
+        self.resort();
+    }
+
+    fn resort(&mut self) {
+        self.refresh_panels();
+        self.status_message = Some(format!("Sort: {}", self.list_options.sort));
     }
 
     // In src/main.rs, inside impl App { ... }
@@ -95,84 +239,100 @@ impl App {
             // Need to scope this borrow
             let selected_item = &self.current_entries[selected_idx];
 
-            // Check if it's a directory. If not, do nothing.
-            if !selected_item
-                .entry
-                .file_type()
-                .map(|ft| ft.is_dir())
-                .unwrap_or(false)
-                {
-                    return;
-                }
-                (selected_item.entry.path(), selected_item.depth)
+            // Check if it's a directory (or a link to one). If not, do nothing.
+            if !is_dir_entry(&selected_item.entry) {
+                return;
+            }
+            (selected_item.entry.path(), selected_item.depth)
         };
 
         // Check if it's already unfolded by peeking at the next item
         let is_unfolded = self
         .current_entries
         .get(selected_idx + 1)
-        .map_or(false, |next_item| next_item.depth > current_depth);
+        .is_some_and(|next_item| next_item.depth > current_depth);
+
+        // Still being listed counts as open: folding it drops the listing
+        let is_unfolded = is_unfolded || self.tree_loading.contains_key(&current_path);
 
         if is_unfolded {
             // --- Collapse (Refold) ---
             // Find the end of the items to remove.
-            // We remove everything after this item until we hit an item with
-            // a depth <= to the current item's depth.
-            let end_range = self
-            .current_entries
-            .iter()
-            .skip(selected_idx + 1)
-            // Find the index of the *first* item that is NOT a child
-            .position(|item| item.depth <= current_depth)
-            // If we find one, its index is relative to (selected_idx + 1)
-            .map_or(self.current_entries.len(), |i| i + selected_idx + 1);
+            let end_range = self.children_end(selected_idx);
 
             if end_range > selected_idx + 1 {
                 self.current_entries.drain(selected_idx + 1..end_range);
             }
+            self.tree_loading.remove(&current_path);
+            // Folders open inside it stay open for when it is unfolded again
+            if self.recursive_view {
+                self.folds.close(&self.current_path, &current_path);
+            }
         } else {
             // --- Expand (Unfold) ---
-            // We need to recursively build the tree for this folder
-            let mut new_entries = Vec::new();
-
-            // We use our existing recursive builder, starting at depth + 1
-            // We ignore errors here (e.g., permissions)
-            let _ = build_recursive_tree(&current_path, current_depth + 1, &mut new_entries);
-
-            if !new_entries.is_empty() {
-                // Insert the new entries right after the selected item
-                self.current_entries
-                .splice(selected_idx + 1..selected_idx + 1, new_entries);
+            if !self.may_open(current_depth) {
+                self.status_message = Some(format!(
+                    "The tree view shows at most {} levels ([tree] max_depth)",
+                    self.config.tree.max_depth
+                ));
+                return;
             }
+            // Its entries are listed in the background. The tree view then
+            // opens the folders that were open inside it; the flat view
+            // shows just the one level.
+            if self.recursive_view {
+                self.folds.open(&self.current_path, &current_path);
+            }
+            self.load_children(current_path, true);
         }
     }
+
+    // Index just past the unfolded children of the entry at `index`.
+    // We remove everything after this item until we hit an item with
+    // a depth <= to the current item's depth.
+    pub fn children_end(&self, index: usize) -> usize {
+        let depth = self.current_entries[index].depth;
+        self
+        .current_entries
+        .iter()
+        .skip(index + 1)
+        // Find the index of the *first* item that is NOT a child
+        .position(|item| item.depth <= depth)
+        // If we find one, its index is relative to (index + 1)
+        .map_or(self.current_entries.len(), |i| i + index + 1)
+    }
+
     // Go "into" a directory (like `cd <dir>` or `l`)
     pub fn enter_directory(&mut self) {
-        if let Some(selected_idx) = self.current_selected.selected() {
-            if let Some(tree_entry) = self.current_entries.get(selected_idx) {
-                if tree_entry
-                    .entry
-                    .file_type()
-                    .map(|ft| ft.is_dir())
-                    .unwrap_or(false)
-                    {
-                        // Use the entry's full path
-                        self.current_path = tree_entry.entry.path();
-                        // Entering a directory always resets the view to flat
-                        self.recursive_view = false;
-                        self.update_panels();
-                    }
-            }
+        if let Some(selected_idx) = self.current_selected.selected()
+            && let Some(tree_entry) = self.current_entries.get(selected_idx)
+            // Links to directories are entered under their own path, so
+            // going back up leads where they were
+            && is_dir_entry(&tree_entry.entry)
+        {
+            // Use the entry's full path
+            let path = tree_entry.entry.path();
+            self.push_history();
+            self.current_path = path;
+            // Entering a directory always resets the view to flat
+            self.recursive_view = false;
+            self.current_selected.select(Some(0));
+            self.update_panels();
         }
     }
 
     // Go "up" a directory (like `cd ..` or `h`)
     pub fn leave_directory(&mut self) {
-        if self.current_path.pop() {
-            // Leaving a directory also resets the view to flat
-            self.recursive_view = false;
-            self.update_panels();
-        }
+        let Some(parent) = self.current_path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        self.push_history();
+        let child = std::mem::replace(&mut self.current_path, parent);
+        // Leaving a directory also resets the view to flat
+        self.recursive_view = false;
+        self.update_panels();
+        // Keep the directory we came from selected
+        self.select_entry_path(&child);
     }
 
     // Move selection down (like `j`)
@@ -221,8 +381,16 @@ impl App {
 
     // ... inside impl App { ... }
     pub fn update_preview(&mut self) {
+        self.load_preview();
+        // Keep an active search highlighted in the new preview
+        self.rerun_preview_search();
+    }
+
+    fn load_preview(&mut self) {
         // Reset scroll when selection changes
         self.preview_scroll_offset = 0; // CHANGED
+        self.preview_modified = None;
+        self.selected_link = None;
 
         let selected_entry = match self.get_selected_entry() {
             Some(entry) => entry,
@@ -233,34 +401,149 @@ impl App {
         };
 
         // Check if it's a directory
-        if selected_entry.metadata().map(|m| m.is_dir()).unwrap_or(false) {
+        if is_dir_entry(selected_entry) {
             self.preview_content = PreviewState::Text(get_entry_info(selected_entry));
             return;
         }
 
         // Check if it's a Markdown file
-        let is_markdown = selected_entry
-        .path()
-        .extension()
-        .and_then(OsStr::to_str)
-        .map_or(false, |ext| ext.eq_ignore_ascii_case("md"));
-
-        if is_markdown {
-            match fs::read_to_string(selected_entry.path()) {
-                Ok(content) => {
-                    self.preview_content = PreviewState::Markdown(content);
-                }
-                Err(e) => {
-                    self.preview_content =
-                    PreviewState::Text(format!("Error reading file:\n{}", e));
-                }
-            }
+        let path = selected_entry.path();
+        if self.is_markdown_path(&path) {
+            self.load_markdown(&path);
         } else {
             // Not markdown, just show regular info
             self.preview_content = PreviewState::Text(get_entry_info(selected_entry));
         }
     }
 
+    // Render a Markdown file into the preview
+    fn load_markdown(&mut self, path: &Path) {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                self.preview_modified = modified;
+                self.show_markdown(content);
+            }
+            Err(e) => {
+                self.preview_content =
+                PreviewState::Text(format!("Error reading file:\n{}", e));
+            }
+        }
+    }
+
+    // Parse Markdown source into the preview
+    fn show_markdown(&mut self, source: String) {
+        let mut doc = MarkdownDocument::new(source, &self.theme);
+        // Lay out for the pane right away so line numbers
+        // (links, headings) match what will be drawn
+        doc.rewrap(self.preview_area.width);
+        self.preview_content = PreviewState::Markdown(Box::new(doc));
+    }
+
+    // Show a Markdown file in the preview without touching the Current panel
+    // (used for search results)
+    pub fn preview_markdown_file(&mut self, path: &Path) {
+        self.preview_scroll_offset = 0;
+        self.preview_modified = None;
+        self.selected_link = None;
+        self.load_markdown(path);
+    }
+
+    // Preview any path, Markdown or not, without selecting it
+    pub fn preview_path(&mut self, path: &Path) {
+        if path.is_file() && self.is_markdown_path(path) {
+            self.preview_markdown_file(path);
+        } else {
+            self.preview_scroll_offset = 0;
+            self.preview_modified = None;
+            self.selected_link = None;
+            self.preview_content = PreviewState::Text(get_path_info(path));
+        }
+    }
+
+    // The file shown in the preview: the pager's document, the highlighted
+    // finder candidate or search hit while those are listed, otherwise the
+    // selected entry
+    pub fn previewed_path(&self) -> Option<PathBuf> {
+        match &self.pager {
+            Some(PagerSource::File(path)) => return Some(path.clone()),
+            Some(PagerSource::Stdin) => return None,
+            None => {}
+        }
+        if self.finder.is_some() {
+            return self.selected_candidate();
+        }
+        match self.selected_hit() {
+            Some(hit) => Some(hit.path.clone()),
+            None => self.get_selected_entry().map(|entry| entry.path()),
+        }
+    }
+
+
+    // Whether a path should be rendered as Markdown in the preview
+    pub fn is_markdown_path(&self, path: &Path) -> bool {
+        is_markdown_file(path)
+    }
+
+    // Show `path` in the Current panel: browse its parent directory and
+    // select the entry, which also loads it into the preview
+    pub fn select_path(&mut self, path: &Path) {
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        if self.current_path != parent || self.recursive_view {
+            self.current_path = parent.to_path_buf();
+            self.recursive_view = false;
+            self.update_panels();
+        }
+        self.select_entry_path(path);
+    }
+
+    // Scroll the preview just enough to bring `line` into view
+    pub fn scroll_preview_to_line(&mut self, line: usize) {
+        let line = line.min(u16::MAX as usize) as u16;
+        let height = self.preview_area.height.max(1);
+        if line < self.preview_scroll_offset {
+            self.preview_scroll_offset = line;
+        } else if line >= self.preview_scroll_offset.saturating_add(height) {
+            self.preview_scroll_offset = line - height + 1;
+        }
+    }
+
+    // Load the previewed file again, keeping the reader's place in it
+    pub fn reload_preview(&mut self) {
+        // Piped input was read once and cannot be read again
+        if self.pager == Some(PagerSource::Stdin) {
+            return;
+        }
+        let offset = self.preview_scroll_offset;
+        match self.previewed_path() {
+            Some(path) if self.pager.is_some() || self.finder.is_some() || self.selected_hit().is_some() => {
+                self.preview_path(&path);
+                self.rerun_preview_search();
+            }
+            _ => self.update_preview(),
+        }
+        self.preview_scroll_offset = offset;
+    }
+
+    // Re-read the previewed Markdown file if it changed on disk since it was
+    // loaded. Returns true if the preview was refreshed.
+    pub fn refresh_preview_if_modified(&mut self) -> bool {
+        if !matches!(self.preview_content, PreviewState::Markdown(_)) {
+            return false;
+        }
+        let Some(path) = self.previewed_path() else {
+            return false;
+        };
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified == self.preview_modified {
+            return false;
+        }
+
+        self.reload_preview();
+        true
+    }
 
     // CHANGED: Scroll preview panel down
     pub fn scroll_preview_down(&mut self) {
@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Position, Rect};
+
+use super::state::{App, InputMode, PreviewState};
+use crate::fs::is_dir_entry;
+
+// Two clicks on the same cell within this long make a double-click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+// Lines the preview scrolls per step of the wheel
+const WHEEL_LINES: i32 = 3;
+
+// The entry a click landed on, so a second click only opens what the first
+// one selected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClickTarget {
+    // Index into the Current panel's list (entries or search hits)
+    Current(usize),
+    // Index into the Parent panel's list, as it was before the click moved
+    // up to it
+    Parent(usize),
+}
+
+impl App {
+    // Act on a mouse event. Returns false for the ones we ignore (moves,
+    // drags...), which don't need a redraw.
+    pub fn handle_mouse_event(&mut self, event: MouseEvent) -> bool {
+        let position = Position::new(event.column, event.row);
+        match (self.input_mode, event.kind) {
+            // A click anywhere closes the key help, like any key would
+            (InputMode::Help, MouseEventKind::Down(_)) => self.close_help(),
+            // Text entry and the finder keep to the keyboard
+            (InputMode::Normal, MouseEventKind::Down(MouseButton::Left)) => self.click(position),
+            (InputMode::Normal, MouseEventKind::ScrollDown) => self.scroll_wheel(position, 1),
+            (InputMode::Normal, MouseEventKind::ScrollUp) => self.scroll_wheel(position, -1),
+            _ => return false,
+        }
+        true
+    }
+
+    fn click(&mut self, position: Position) {
+        let now = Instant::now();
+        let target = self.click_target(position);
+        let double = self.last_click.is_some_and(|(time, at, first)| {
+            let same_entry = match first {
+                Some(ClickTarget::Current(index)) => target == Some(ClickTarget::Current(index)),
+                // The first click moved up, so the row now shows another list
+                Some(ClickTarget::Parent(_)) => self.parent_area.contains(position),
+                None => false,
+            };
+            at == position && now.duration_since(time) <= DOUBLE_CLICK && same_entry
+        });
+        // A third click starts a new double-click rather than continuing one
+        self.last_click = if double { None } else { Some((now, position, target)) };
+        self.status_message = None;
+        self.pending_keys.clear();
+
+        if self.preview_area.contains(position) {
+            self.click_preview(position);
+            return;
+        }
+
+        // The first click of a double-click already selected the entry (and
+        // for the Parent panel, moved up to it), so open it
+        if double {
+            if self.file_search.is_some() {
+                self.open_selected_hit();
+            } else {
+                self.enter_directory();
+            }
+            return;
+        }
+
+        if let Some(index) = list_row(self.current_area, self.current_list_offset(), position) {
+            if self.file_search.is_some() {
+                self.select_hit(index);
+            } else if index < self.current_entries.len() && self.current_selected.selected() != Some(index) {
+                self.current_selected.select(Some(index));
+                self.update_preview();
+            }
+        } else if let Some(index) = list_row(self.parent_area, self.parent_selected.offset(), position) {
+            self.click_parent(index);
+        } else if let Some(index) = list_row(self.outline_area, self.outline_offset, position) {
+            self.jump_to_heading(index);
+        }
+    }
+
+    // The listed entry under the pointer in the Current or Parent panel
+    fn click_target(&self, position: Position) -> Option<ClickTarget> {
+        let listed = match &self.file_search {
+            Some(search) => search.hits.len(),
+            None => self.current_entries.len(),
+        };
+        if let Some(index) = list_row(self.current_area, self.current_list_offset(), position) {
+            (index < listed).then_some(ClickTarget::Current(index))
+        } else {
+            list_row(self.parent_area, self.parent_selected.offset(), position)
+            .filter(|index| *index < self.parent_entries.len())
+            .map(ClickTarget::Parent)
+        }
+    }
+
+    // Follow the link under the pointer, if there is one
+    fn click_preview(&mut self, position: Position) {
+        let PreviewState::Markdown(doc) = &self.preview_content else {
+            return;
+        };
+        let line = self.preview_scroll_offset as usize + (position.y - self.preview_area.y) as usize;
+        let column = (position.x - self.preview_area.x) as usize;
+        let link = doc.links().iter().position(|link| {
+            link.regions
+            .iter()
+            .any(|region| region.line == line && (region.start..region.end).contains(&column))
+        });
+        if let Some(index) = link {
+            self.selected_link = Some(index);
+            self.follow_selected_link();
+        }
+    }
+
+    // Move up to the parent directory with the clicked entry selected
+    fn click_parent(&mut self, index: usize) {
+        let Some(path) = self.parent_entries.get(index).map(|entry| entry.path()) else {
+            return;
+        };
+        if self.file_search.is_some() {
+            self.close_file_search();
+        }
+        self.leave_directory();
+        self.select_entry_path(&path);
+    }
+
+    // Scroll whichever pane is under the pointer
+    fn scroll_wheel(&mut self, position: Position, step: i32) {
+        if self.preview_area.contains(position) {
+            self.scroll_preview_by(step * WHEEL_LINES);
+        } else if self.current_area.contains(position) {
+            self.step_current_selection(step);
+        } else if self.parent_area.contains(position) {
+            self.step_sibling_directory(step);
+        } else if self.outline_area.contains(position) {
+            if step > 0 {
+                self.next_heading();
+            } else {
+                self.previous_heading();
+            }
+        }
+    }
+
+    // Like j/k, but stopping at either end of the list instead of wrapping
+    fn step_current_selection(&mut self, step: i32) {
+        let (selected, count) = match &self.file_search {
+            Some(search) => (search.selected.selected(), search.hits.len()),
+            None => (self.current_selected.selected(), self.current_entries.len()),
+        };
+        if count == 0 {
+            return;
+        }
+        let index = (selected.unwrap_or(0) as i64 + step as i64).clamp(0, count as i64 - 1) as usize;
+        if self.file_search.is_some() {
+            self.select_hit(index);
+        } else if selected != Some(index) {
+            self.current_selected.select(Some(index));
+            self.update_preview();
+        }
+    }
+
+    // The wheel over the Parent panel moves to the next or previous
+    // directory beside the current one
+    fn step_sibling_directory(&mut self, step: i32) {
+        let Some(current) = self.parent_selected.selected() else {
+            return;
+        };
+        let is_dir = |i: &usize| is_dir_entry(&self.parent_entries[*i]);
+        let sibling = if step > 0 {
+            (current + 1..self.parent_entries.len()).find(is_dir)
+        } else {
+            (0..current).rev().find(is_dir)
+        };
+        let Some(path) = sibling.map(|i| self.parent_entries[i].path()) else {
+            return;
+        };
+        if self.file_search.is_some() {
+            self.close_file_search();
+        }
+        self.push_history();
+        self.current_path = path;
+        self.recursive_view = false;
+        self.current_selected.select(Some(0));
+        self.update_panels();
+    }
+
+    // First row shown in the Current panel, which lists search results
+    // while there are any
+    fn current_list_offset(&self) -> usize {
+        match &self.file_search {
+            Some(search) => search.selected.offset(),
+            None => self.current_selected.offset(),
+        }
+    }
+}
+
+// Index of the list item drawn on the clicked row of a list's inner area
+fn list_row(area: Rect, offset: usize, position: Position) -> Option<usize> {
+    area.contains(position).then(|| offset + (position.y - area.y) as usize)
+}
@@ -0,0 +1,92 @@
+use super::state::{App, PreviewState};
+
+impl App {
+    // Show or hide the outline pane
+    pub fn toggle_outline(&mut self) {
+        self.show_outline = !self.show_outline;
+        self.outline_focus = None;
+    }
+
+    // Give the outline pane the keys, showing it if needed, starting from
+    // the heading in view. The selection keys then move through the
+    // headings and Enter scrolls the preview to the highlighted one.
+    pub fn focus_outline(&mut self) {
+        if self.heading_count() == 0 {
+            self.status_message = Some("No headings to go to".to_string());
+            return;
+        }
+        self.show_outline = true;
+        self.outline_focus = Some(self.current_heading().unwrap_or(0));
+    }
+
+    // Hand the keys back to the panels
+    pub fn unfocus_outline(&mut self) {
+        self.outline_focus = None;
+    }
+
+    // Move the outline's highlight by `step` headings, within the list
+    pub fn move_outline_focus(&mut self, step: isize) {
+        let count = self.heading_count();
+        match self.outline_focus {
+            // The document changed under the outline and has none left
+            _ if count == 0 => self.outline_focus = None,
+            Some(index) => self.outline_focus = Some(index.saturating_add_signed(step).min(count - 1)),
+            None => {}
+        }
+    }
+
+    // Scroll the preview to the highlighted heading and leave the outline
+    pub fn open_focused_heading(&mut self) {
+        if let Some(index) = self.outline_focus.take() {
+            self.jump_to_heading(index);
+        }
+    }
+
+    fn heading_count(&self) -> usize {
+        match &self.preview_content {
+            PreviewState::Markdown(doc) => doc.headings().len(),
+            _ => 0,
+        }
+    }
+
+    // Index of the heading whose section is at the top of the preview
+    pub fn current_heading(&self) -> Option<usize> {
+        let PreviewState::Markdown(doc) = &self.preview_content else {
+            return None;
+        };
+        let offset = self.preview_scroll_offset as usize;
+        doc.headings().iter().rposition(|heading| heading.line <= offset)
+    }
+
+    // Scroll the preview to the next heading below the top of the view
+    pub fn next_heading(&mut self) {
+        let PreviewState::Markdown(doc) = &self.preview_content else {
+            return;
+        };
+        let offset = self.preview_scroll_offset as usize;
+        if let Some(heading) = doc.headings().iter().find(|heading| heading.line > offset) {
+            self.preview_scroll_offset = heading.line.min(u16::MAX as usize) as u16;
+        }
+    }
+
+    // Scroll the preview to the previous heading above the top of the view
+    pub fn previous_heading(&mut self) {
+        let PreviewState::Markdown(doc) = &self.preview_content else {
+            return;
+        };
+        let offset = self.preview_scroll_offset as usize;
+        if let Some(heading) = doc.headings().iter().rev().find(|heading| heading.line < offset) {
+            self.preview_scroll_offset = heading.line.min(u16::MAX as usize) as u16;
+        }
+    }
+
+    // Scroll the preview to a heading chosen in the outline
+    pub fn jump_to_heading(&mut self, index: usize) {
+        let PreviewState::Markdown(doc) = &self.preview_content else {
+            return;
+        };
+        if let Some(heading) = doc.headings().get(index) {
+            self.preview_scroll_offset = heading.line.min(u16::MAX as usize) as u16;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use super::state::{App, PagerSource};
+
+impl App {
+    // Show a single document on its own, with the whole terminal
+    // (`greenmerald view`)
+    pub fn open_in_pager(&mut self, path: &Path) {
+        self.pager = Some(PagerSource::File(path.to_path_buf()));
+        self.preview_path(path);
+        self.rerun_preview_search();
+    }
+
+    // Show Markdown read from standard input in the pager
+    pub fn open_stdin_in_pager(&mut self, source: String) {
+        self.pager = Some(PagerSource::Stdin);
+        self.preview_scroll_offset = 0;
+        self.preview_modified = None;
+        self.selected_link = None;
+        self.show_markdown(source);
+    }
+
+    // Number of rendered lines in the preview
+    pub fn preview_line_count(&self) -> usize {
+        self.with_preview_lines(|lines| lines.len())
+    }
+
+    // Scroll by `lines` (negative scrolls up), stopping with the last line at
+    // the bottom of the screen rather than scrolling past the end. The paging
+    // keys use this for the browser's preview pane too.
+    pub fn scroll_preview_by(&mut self, lines: i32) {
+        let height = self.preview_area.height as usize;
+        let last = self.preview_line_count().saturating_sub(height);
+        let offset = (self.preview_scroll_offset as i64 + lines as i64).clamp(0, last as i64);
+        self.preview_scroll_offset = offset.min(u16::MAX as i64) as u16;
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_preview_by(self.page_height());
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_preview_by(-self.page_height());
+    }
+
+    pub fn half_page_down(&mut self) {
+        self.scroll_preview_by((self.page_height() / 2).max(1));
+    }
+
+    pub fn half_page_up(&mut self) {
+        self.scroll_preview_by(-(self.page_height() / 2).max(1));
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.preview_scroll_offset = 0;
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_preview_by(i32::MAX);
+    }
+
+    // First and last line on screen (1-based) and the total number of lines
+    pub fn pager_position(&self) -> (usize, usize, usize) {
+        let total = self.preview_line_count();
+        let top = (self.preview_scroll_offset as usize).min(total);
+        let bottom = (top + self.preview_area.height as usize).min(total);
+        (top + 1, bottom, total)
+    }
+
+    fn page_height(&self) -> i32 {
+        self.preview_area.height.max(1) as i32
+    }
+}
@@ -0,0 +1,219 @@
+use ratatui::text::Line;
+use regex::{Regex, RegexBuilder};
+use unicode_width::UnicodeWidthStr;
+
+use super::state::{App, InputMode, PreviewState};
+use crate::ui::markdown::wrap_text;
+
+// A match in the rendered preview, in columns of a rendered line
+pub struct SearchMatch {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// State of a `/` search in the preview pane
+#[derive(Default)]
+pub struct PreviewSearch {
+    pub query: String,
+    // Treat the query as a regular expression instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub matches: Vec<SearchMatch>,
+    // Index into `matches` of the match we jumped to
+    pub current: Option<usize>,
+    // Set when the query is not a valid regex
+    pub error: Option<String>,
+    // Scroll offset when the search started; typing searches forward from here
+    origin: u16,
+}
+
+impl PreviewSearch {
+    fn build_regex(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        RegexBuilder::new(&pattern)
+        .case_insensitive(!self.case_sensitive)
+        .build()
+    }
+}
+
+// Finds every match of `regex` in the rendered lines
+fn find_matches(lines: &[Line<'_>], regex: &Regex) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+        for found in regex.find_iter(&text) {
+            // Empty matches (e.g. "a*") would highlight nothing
+            if found.is_empty() {
+                continue;
+            }
+            let start = text[..found.start()].width();
+            matches.push(SearchMatch {
+                line: index,
+                start,
+                end: start + found.as_str().width(),
+            });
+        }
+    }
+    matches
+}
+
+impl App {
+    // Enter search mode (`/`)
+    pub fn start_preview_search(&mut self) {
+        self.input_mode = InputMode::PreviewSearch;
+        let search = self.preview_search.get_or_insert_with(PreviewSearch::default);
+        search.query.clear();
+        search.matches.clear();
+        search.current = None;
+        search.error = None;
+        search.origin = self.preview_scroll_offset;
+    }
+
+    // Highlight `query` in the preview without entering search mode, jumping
+    // to the first match at or below the current scroll position
+    pub fn search_preview_for(&mut self, query: &str) {
+        self.preview_search = Some(PreviewSearch {
+            query: query.to_string(),
+            origin: self.preview_scroll_offset,
+            ..PreviewSearch::default()
+        });
+        self.run_preview_search(true);
+    }
+
+    // Leave search mode, keeping the matches (Enter)
+    pub fn confirm_preview_search(&mut self) {
+        self.input_mode = InputMode::Normal;
+        if self.preview_search.as_ref().is_some_and(|search| search.query.is_empty()) {
+            self.preview_search = None;
+        }
+    }
+
+    // Leave search mode and drop the search (Esc)
+    pub fn cancel_preview_search(&mut self) {
+        if let Some(search) = self.preview_search.take()
+            && self.input_mode == InputMode::PreviewSearch
+        {
+            self.preview_scroll_offset = search.origin;
+        }
+        self.input_mode = InputMode::Normal;
+    }
+
+    pub fn push_search_char(&mut self, c: char) {
+        if let Some(search) = &mut self.preview_search {
+            search.query.push(c);
+        }
+        self.run_preview_search(true);
+    }
+
+    pub fn pop_search_char(&mut self) {
+        if let Some(search) = &mut self.preview_search {
+            search.query.pop();
+        }
+        self.run_preview_search(true);
+    }
+
+    pub fn toggle_search_regex(&mut self) {
+        if let Some(search) = &mut self.preview_search {
+            search.regex = !search.regex;
+        }
+        self.run_preview_search(true);
+    }
+
+    pub fn toggle_search_case(&mut self) {
+        if let Some(search) = &mut self.preview_search {
+            search.case_sensitive = !search.case_sensitive;
+        }
+        self.run_preview_search(true);
+    }
+
+    // Recompute matches against the current preview. With `jump`, move to
+    // the first match at or below where the search started.
+    pub fn run_preview_search(&mut self, jump: bool) {
+        let Some(search) = &self.preview_search else {
+            return;
+        };
+        if search.query.is_empty() {
+            let origin = search.origin;
+            if let Some(search) = &mut self.preview_search {
+                search.matches.clear();
+                search.current = None;
+                search.error = None;
+            }
+            if jump {
+                self.preview_scroll_offset = origin;
+            }
+            return;
+        }
+
+        let (matches, error) = match search.build_regex() {
+            Ok(regex) => (self.with_preview_lines(|lines| find_matches(lines, &regex)), None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        let origin = search.origin as usize;
+        let current = if matches.is_empty() {
+            None
+        } else {
+            Some(matches.iter().position(|m| m.line >= origin).unwrap_or(0))
+        };
+        let target = current.map(|i| matches[i].line);
+
+        if let Some(search) = &mut self.preview_search {
+            search.matches = matches;
+            search.error = error;
+            search.current = current;
+        }
+        if jump && let Some(line) = target {
+            self.scroll_preview_to_line(line);
+        }
+    }
+
+    // Search the newly loaded (or re-wrapped) preview for the same query,
+    // counting matches from where the reader is now
+    pub fn rerun_preview_search(&mut self) {
+        if let Some(search) = &mut self.preview_search {
+            search.origin = self.preview_scroll_offset;
+        }
+        self.run_preview_search(false);
+    }
+
+    // Jump to the next match (`n`), wrapping around
+    pub fn next_search_match(&mut self) {
+        self.step_search_match(1);
+    }
+
+    // Jump to the previous match (`N`), wrapping around
+    pub fn previous_search_match(&mut self) {
+        self.step_search_match(-1);
+    }
+
+    fn step_search_match(&mut self, step: isize) {
+        let Some(search) = &mut self.preview_search else {
+            return;
+        };
+        let count = search.matches.len();
+        if count == 0 {
+            return;
+        }
+        let next = match search.current {
+            Some(i) => (i as isize + step).rem_euclid(count as isize) as usize,
+            None => 0,
+        };
+        search.current = Some(next);
+        let line = search.matches[next].line;
+        self.scroll_preview_to_line(line);
+    }
+
+    // Runs `f` over the lines exactly as the preview draws them
+    pub fn with_preview_lines<R>(&self, f: impl FnOnce(&[Line<'static>]) -> R) -> R {
+        match &self.preview_content {
+            PreviewState::None => f(&[]),
+            PreviewState::Text(text) => f(&wrap_text(text, self.preview_area.width as usize)),
+            PreviewState::Markdown(doc) => f(&doc.text().lines),
+        }
+    }
+}
@@ -1,10 +1,49 @@
-use ratatui::widgets::ListState;
-use std::{fs, path::PathBuf};
+use ratatui::{layout::{Position, Rect}, widgets::ListState};
+use std::{collections::HashMap, fs, path::PathBuf, time::{Instant, SystemTime}};
+use super::folds::Folds;
+use super::history::History;
+use super::keymap::{KeyCombo, Keymap};
+use super::links::Openers;
+use super::mouse::ClickTarget;
+use super::file_search::FileSearch;
+use super::export::Exporting;
+use super::finder::Finder;
+use super::search::PreviewSearch;
+use crate::config::Config;
+use crate::fs::ListOptions;
+use crate::fs::tree::TreeLoader;
+use crate::fs::watch::DirWatcher;
+use crate::ui::markdown::MarkdownDocument;
+use crate::ui::theme::Theme;
 
 pub enum PreviewState {
     None,
     Text(String), // For plain text info
-    Markdown(String), // For markdown file content
+    Markdown(Box<MarkdownDocument>), // Parsed once, re-wrapped only on resize
+}
+
+// What the pager is showing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PagerSource {
+    File(PathBuf),
+    // Markdown piped in on standard input; there is no file to reload or edit
+    Stdin,
+}
+
+// Where key presses go
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputMode {
+    Normal,
+    // Typing a `/` query for the preview pane
+    PreviewSearch,
+    // Typing a query to search every Markdown file under the current directory
+    FileSearch,
+    // The Ctrl+p fuzzy finder popup is open
+    Finder,
+    // The '?' key help overlay is open
+    Help,
+    // Waiting for the depth to collapse the tree view to
+    FoldDepth,
 }
 
 // ## 1. Application State ##
@@ -28,4 +67,85 @@ pub struct App {
     pub parent_selected: ListState,
     pub preview_content: PreviewState,
     pub preview_scroll_offset: u16, // CHANGED from preview_scroll
+    // Modification time of the previewed file, used to detect edits
+    pub preview_modified: Option<SystemTime>,
+    pub theme: Theme,
+    // Link focused with Tab/Shift-Tab in the previewed document
+    pub selected_link: Option<usize>,
+    // Command used to open external links
+    pub link_opener: String,
+    // Openers still running for external links
+    pub openers: Openers,
+    // One-off message shown in the header until the next key press
+    pub status_message: Option<String>,
+    // Inner area of the preview pane, updated on every draw
+    pub preview_area: Rect,
+    // Inner areas of the other panes, for mouse clicks. Empty while a pane
+    // is not shown.
+    pub parent_area: Rect,
+    pub current_area: Rect,
+    pub outline_area: Rect,
+    // First heading listed in the outline pane, as last drawn
+    pub outline_offset: usize,
+    // The heading highlighted while the outline pane has the keys
+    pub outline_focus: Option<usize>,
+    // Time and place of the last click and the entry it landed on, to spot
+    // double-clicks
+    pub last_click: Option<(Instant, Position, Option<ClickTarget>)>,
+    // Back/forward navigation stacks
+    pub history: History,
+    // Whether the document outline pane is visible
+    pub show_outline: bool,
+    pub input_mode: InputMode,
+    // Active `/` search in the preview, kept after Enter for n/N
+    pub preview_search: Option<PreviewSearch>,
+    // Search across files; its results replace the Current panel while set
+    pub file_search: Option<FileSearch>,
+    // Fuzzy file finder popup, while it is open
+    pub finder: Option<Finder>,
+    // Directory export running in the background
+    pub exporting: Option<Exporting>,
+    // Reports changes to the current directory and the previewed document.
+    // None if the platform watcher could not be started.
+    pub watcher: Option<DirWatcher>,
+    // Which entries the panels list (hidden files...)
+    pub list_options: ListOptions,
+    // Document shown full-screen without the panels (`greenmerald view`)
+    pub pager: Option<PagerSource>,
+    // Settings from the config file
+    pub config: Config,
+    // Key bindings, the defaults with the config's `[keys]` applied
+    pub keymap: Keymap,
+    // Keys of a sequence typed so far ("g" of "gg")
+    pub pending_keys: Vec<KeyCombo>,
+    // First line shown in the key help overlay
+    pub help_scroll: u16,
+    // Lists directories of the tree in the background, once one is
+    // unfolded; dropping it cancels what is queued
+    pub tree_loader: Option<TreeLoader>,
+    // Directories asked of `tree_loader` and not listed yet, and whether
+    // the user unfolded them
+    pub tree_loading: HashMap<PathBuf, bool>,
+    // Entry to select once the directory holding it is listed, and the
+    // preview scroll to put back once it is
+    pub pending_selection: Option<(PathBuf, Option<u16>)>,
+    // Open directories of the recursive view, for each directory it was
+    // shown from
+    pub folds: Folds,
+}
+
+// How the app starts, usually built from the command line
+pub struct StartOptions {
+    // Directory to browse
+    pub path: PathBuf,
+    // Entry to select in it, e.g. a Markdown file given on the command line
+    pub select: Option<PathBuf>,
+    pub recursive: bool,
+    pub list_options: ListOptions,
+    pub theme: Theme,
+    // Open this document in the pager instead of browsing
+    pub pager: Option<PathBuf>,
+    // Markdown read from standard input (`greenmerald -`), shown in the pager
+    pub stdin: Option<String>,
+    pub config: Config,
 }
@@ -0,0 +1,31 @@
+use super::state::{App, PreviewState};
+use crate::ui::theme::Theme;
+
+impl App {
+    // Switch to the next built-in or user theme, for the session only.
+    // User theme files that fail to load are skipped, with a message.
+    pub fn cycle_theme(&mut self) {
+        let names = Theme::available();
+        let current = names.iter().position(|name| *name == self.theme.name);
+        let start = current.map_or(0, |i| i + 1);
+        let mut skipped = None;
+
+        for name in names.iter().cycle().skip(start).take(names.len()) {
+            match Theme::load(name) {
+                Ok(theme) => {
+                    self.theme = theme.with_colors(&self.config.colors);
+                    // The document keeps the theme it was rendered with
+                    if let PreviewState::Markdown(doc) = &mut self.preview_content {
+                        doc.set_theme(&self.theme);
+                    }
+                    self.status_message = Some(match skipped {
+                        Some(e) => format!("Theme: {name} (skipped {e})"),
+                        None => format!("Theme: {name}"),
+                    });
+                    return;
+                }
+                Err(e) => skipped = Some(e),
+            }
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::state::{App, TreeEntry};
+use crate::fs::is_dir_entry;
+use crate::fs::tree::TreeLoader;
+
+impl App {
+    // Whether directories of the tree are still being listed
+    pub fn is_loading_tree(&self) -> bool {
+        !self.tree_loading.is_empty()
+    }
+
+    // Have `dir` listed in the background, to show its entries below it.
+    // What the user unfolded is shown even past `max_entries`.
+    pub fn load_children(&mut self, dir: PathBuf, unfolded: bool) {
+        let options = self.list_options;
+        self
+        .tree_loader
+        .get_or_insert_with(|| TreeLoader::new(options))
+        .request(dir.clone());
+        self.tree_loading.insert(dir, unfolded);
+    }
+
+    // Forget the directories still being listed for the old listing
+    pub fn stop_loading_tree(&mut self) {
+        self.tree_loader = None;
+        self.tree_loading.clear();
+        self.pending_selection = None;
+    }
+
+    // Add whatever was listed since the last call. Returns true if anything
+    // came in or is still coming, so the spinner keeps turning.
+    pub fn poll_tree_walk(&mut self) -> bool {
+        let Some(loader) = &self.tree_loader else {
+            return false;
+        };
+        let mut received = false;
+        let mut listings = HashMap::new();
+        for listing in loader.receiver.try_iter() {
+            received = true;
+            // Directories folded in the meantime are left folded
+            if let Some(unfolded) = self.tree_loading.remove(&listing.dir)
+                && let Some(entries) = listing.entries
+            {
+                listings.insert(listing.dir, Listed { entries, unfolded });
+            }
+        }
+
+        if !listings.is_empty() {
+            self.insert_listings(listings);
+        }
+        // Keeps waiting while directories are being listed
+        if let Some((path, scroll)) = self.pending_selection.take() {
+            if self.select_entry_path(&path) {
+                if let Some(scroll) = scroll {
+                    self.preview_scroll_offset = scroll;
+                }
+            } else if let Some(pending) = &mut self.pending_selection {
+                pending.1 = scroll;
+            }
+        }
+        received || self.is_loading_tree()
+    }
+
+    // Put each listed directory's entries right below it, in place of the
+    // ones it had, keeping the same entry selected
+    fn insert_listings(&mut self, listings: HashMap<PathBuf, Listed>) {
+        let selected = self.get_selected_entry().map(|entry| entry.path());
+        let old = std::mem::take(&mut self.current_entries);
+        let mut splice = Splice::new(self, listings, old.len());
+        splice.relist(old);
+        let spliced = splice.finish();
+        self.apply_splice(spliced);
+
+        let Some(selected) = selected else {
+            return;
+        };
+        // Or the folder it ended up in, if it is not listed any more
+        let index = selected
+        .ancestors()
+        .take_while(|path| *path != self.current_path)
+        .find_map(|path| self.entry_index(path));
+        if let Some(index) = index {
+            self.current_selected.select(Some(index));
+        } else {
+            self.clamp_selection();
+        }
+        if self.get_selected_entry().map(|entry| entry.path()) != Some(selected) {
+            self.update_preview();
+        }
+    }
+
+    // Show the entries a splice put together, and list the open
+    // directories it came across
+    pub(super) fn apply_splice(&mut self, spliced: Spliced) {
+        let Spliced { entries, to_load, mut truncated } = spliced;
+        self.current_entries = entries;
+        for dir in to_load {
+            if self.current_entries.len() >= self.config.tree.max_entries {
+                truncated = true;
+                break;
+            }
+            self.load_children(dir, false);
+        }
+        if truncated {
+            self.status_message = Some(format!(
+                "Tree view stopped at {} entries ('t' opens a folded directory)",
+                self.config.tree.max_entries
+            ));
+        }
+    }
+
+    // Whether the tree view shows `dir` open without being asked
+    fn opens(&self, dir: &Path) -> bool {
+        self.recursive_view && self.folds.is_open(&self.current_path, dir)
+    }
+
+    // Whether a directory listed `depth` levels down may be opened. Its
+    // entries would be on level `depth + 2`, counting the current
+    // directory's own as level 1.
+    pub fn may_open(&self, depth: usize) -> bool {
+        depth + 2 <= self.config.tree.max_depth
+    }
+
+    // Keep the selection within the list, or select the first entry
+    pub fn clamp_selection(&mut self) {
+        if self.current_entries.is_empty() {
+            self.current_selected.select(None);
+        } else {
+            let last = self.current_entries.len() - 1;
+            let selected = self.current_selected.selected().map_or(0, |selected| selected.min(last));
+            self.current_selected.select(Some(selected));
+        }
+    }
+}
+
+// The Current panel's entries put together again with new listings in
+// place of what the listed directories showed before
+pub(super) struct Splice<'a> {
+    app: &'a App,
+    listings: HashMap<PathBuf, Listed>,
+    entries: Vec<TreeEntry>,
+    // Open directories come across in new listings, to be listed in turn
+    to_load: Vec<PathBuf>,
+    // Entries that can still be added before `max_entries`
+    budget: usize,
+    truncated: bool,
+}
+
+// A directory's new entries
+pub(super) struct Listed {
+    entries: Vec<fs::DirEntry>,
+    // Whether the user unfolded it, rather than it being open already
+    unfolded: bool,
+}
+
+// What a splice leaves once done
+pub(super) struct Spliced {
+    entries: Vec<TreeEntry>,
+    to_load: Vec<PathBuf>,
+    truncated: bool,
+}
+
+impl<'a> Splice<'a> {
+    pub(super) fn new(app: &'a App, listings: HashMap<PathBuf, Listed>, listed: usize) -> Splice<'a> {
+        Splice {
+            app,
+            listings,
+            entries: Vec::with_capacity(listed),
+            to_load: Vec::new(),
+            budget: app.config.tree.max_entries.saturating_sub(listed),
+            truncated: false,
+        }
+    }
+
+    pub(super) fn finish(self) -> Spliced {
+        Spliced {
+            entries: self.entries,
+            to_load: self.to_load,
+            truncated: self.truncated,
+        }
+    }
+
+    // Go through the old entries, replacing what is inside each listed
+    // directory. Ones not listed any more (folded meanwhile) are skipped.
+    fn relist(&mut self, old: Vec<TreeEntry>) {
+        let mut old = old.into_iter().peekable();
+        while let Some(tree_entry) = old.next() {
+            let depth = tree_entry.depth;
+            let listed = if is_dir_entry(&tree_entry.entry) {
+                self.listings.remove(&tree_entry.entry.path())
+            } else {
+                None
+            };
+            self.entries.push(tree_entry);
+            if let Some(listed) = listed {
+                let mut inside = Vec::new();
+                while let Some(next) = old.next_if(|next| next.depth > depth) {
+                    inside.push(next);
+                }
+                self.push_children(listed, depth + 1, inside);
+            }
+        }
+    }
+
+    // Add a subdirectory's entries. One that showed none before and was
+    // not unfolded by the user is left folded past `max_entries`.
+    fn push_children(&mut self, listed: Listed, depth: usize, inside: Vec<TreeEntry>) {
+        if inside.is_empty() && !listed.unfolded {
+            if self.budget == 0 {
+                self.truncated = true;
+                return;
+            }
+            self.budget = self.budget.saturating_sub(listed.entries.len());
+        }
+        self.push_listing(listed.entries, depth, inside);
+    }
+
+    // Add `listed` as the entries at `depth`, in place of `old`: the ones
+    // that were there, each followed by its own. Subdirectories that stay
+    // open keep what they showed until they are listed again.
+    pub(super) fn push_listing(&mut self, listed: Vec<fs::DirEntry>, depth: usize, old: Vec<TreeEntry>) {
+        let mut kept: HashMap<PathBuf, Vec<TreeEntry>> = HashMap::new();
+        let mut old = old.into_iter().peekable();
+        while let Some(tree_entry) = old.next() {
+            let mut inside = Vec::new();
+            while let Some(next) = old.next_if(|next| next.depth > depth) {
+                inside.push(next);
+            }
+            if !inside.is_empty() {
+                kept.insert(tree_entry.entry.path(), inside);
+            }
+        }
+
+        for entry in listed {
+            let dir = is_dir_entry(&entry).then(|| entry.path());
+            self.entries.push(TreeEntry { entry, depth });
+            let Some(dir) = dir else {
+                continue;
+            };
+            let inside = kept.remove(&dir).unwrap_or_default();
+            if let Some(listed) = self.listings.remove(&dir) {
+                self.push_children(listed, depth + 1, inside);
+            } else if self.app.opens(&dir) && self.app.may_open(depth) {
+                self.entries.extend(inside);
+                self.to_load.push(dir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::app::state::StartOptions;
+    use crate::config::Config;
+
+    // A fresh directory holding the given files (and the directories above
+    // them), removed again when dropped
+    struct TempTree(PathBuf);
+
+    impl TempTree {
+        fn new(name: &str, files: &[&str]) -> TempTree {
+            let root = std::env::temp_dir().join(format!("greenmerald-tree-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            for file in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+            TempTree(root)
+        }
+
+        // The tree view of it, with nothing open yet
+        fn app(&self, max_entries: usize) -> App {
+            let mut config = Config::default();
+            config.tree.max_entries = max_entries;
+            App::with_options(StartOptions {
+                path: self.0.clone(),
+                recursive: true,
+                config,
+                ..StartOptions::default()
+            })
+        }
+    }
+
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Wait for the background listings to be spliced in
+    fn settle(app: &mut App) {
+        let start = Instant::now();
+        while app.is_loading_tree() {
+            assert!(start.elapsed() < Duration::from_secs(5), "the tree view never finished loading");
+            app.poll_tree_walk();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Each entry's name and depth, as the Current panel lists them
+    fn listed(app: &App) -> Vec<(String, usize)> {
+        app
+        .current_entries
+        .iter()
+        .map(|tree_entry| (tree_entry.entry.file_name().to_string_lossy().into_owned(), tree_entry.depth))
+        .collect()
+    }
+
+    fn select(app: &mut App, path: &Path) {
+        let index = app.entry_index(path).expect("the entry is listed");
+        app.current_selected.select(Some(index));
+    }
+
+    fn entries(names: &[(&str, usize)]) -> Vec<(String, usize)> {
+        names.iter().map(|(name, depth)| (name.to_string(), *depth)).collect()
+    }
+
+    #[test]
+    fn unfolded_directories_are_spliced_in_below_themselves() {
+        let tree = TempTree::new("splice", &["a/b/deep.md", "a/x.md", "c.md"]);
+        let mut app = tree.app(1000);
+        assert_eq!(listed(&app), entries(&[("a", 0), ("c.md", 0)]));
+
+        select(&mut app, &tree.0.join("a"));
+        app.toggle_fold();
+        settle(&mut app);
+        assert_eq!(listed(&app), entries(&[("a", 0), ("b", 1), ("x.md", 1), ("c.md", 0)]));
+
+        select(&mut app, &tree.0.join("a/b"));
+        app.toggle_fold();
+        settle(&mut app);
+        assert_eq!(
+            listed(&app),
+            entries(&[("a", 0), ("b", 1), ("deep.md", 2), ("x.md", 1), ("c.md", 0)])
+        );
+        // The selection stayed on the unfolded directory
+        assert_eq!(app.get_selected_entry().map(|entry| entry.path()), Some(tree.0.join("a/b")));
+
+        // Folding `a` takes everything below it away
+        select(&mut app, &tree.0.join("a"));
+        app.toggle_fold();
+        assert_eq!(listed(&app), entries(&[("a", 0), ("c.md", 0)]));
+    }
+
+    #[test]
+    fn reopening_stops_at_max_entries_but_t_still_unfolds() {
+        let tree = TempTree::new("limit", &["a/1.md", "a/2.md", "a/3.md", "b/1.md", "b/2.md", "c.md"]);
+        let mut app = tree.app(6);
+        app.expand_all();
+        settle(&mut app);
+        // `a` fills the six entries, so `b` is left folded
+        assert_eq!(
+            listed(&app),
+            entries(&[("a", 0), ("1.md", 1), ("2.md", 1), ("3.md", 1), ("b", 0), ("c.md", 0)])
+        );
+        assert!(app.status_message.as_deref().is_some_and(|message| message.contains("6 entries")));
+
+        select(&mut app, &tree.0.join("b"));
+        app.toggle_fold();
+        settle(&mut app);
+        assert_eq!(listed(&app).len(), 8);
+    }
+
+    #[test]
+    fn nothing_opens_past_max_depth() {
+        let tree = TempTree::new("depth", &["a/b/deep.md", "a/x.md"]);
+        let mut app = tree.app(1000);
+        app.config.tree.max_depth = 2;
+        app.expand_all();
+        settle(&mut app);
+        assert_eq!(listed(&app), entries(&[("a", 0), ("b", 1), ("x.md", 1)]));
+
+        select(&mut app, &tree.0.join("a/b"));
+        app.toggle_fold();
+        settle(&mut app);
+        assert_eq!(listed(&app).len(), 3);
+    }
+
+    #[test]
+    fn folding_keeps_the_selection_on_a_listed_entry() {
+        let tree = TempTree::new("clamp", &["a/b/deep.md", "a/x.md", "c.md"]);
+        let mut app = tree.app(1000);
+        app.expand_all();
+        settle(&mut app);
+        assert_eq!(listed(&app).len(), 5);
+
+        // The selection moves up to the folder its entry disappeared into
+        select(&mut app, &tree.0.join("a/b/deep.md"));
+        app.collapse_all();
+        settle(&mut app);
+        assert_eq!(listed(&app), entries(&[("a", 0), ("c.md", 0)]));
+        assert_eq!(app.get_selected_entry().map(|entry| entry.path()), Some(tree.0.join("a")));
+
+        // ...or stays within the list when the last entry goes
+        select(&mut app, &tree.0.join("c.md"));
+        fs::remove_file(tree.0.join("c.md")).unwrap();
+        app.load_current_entries(true);
+        assert_eq!(app.current_selected.selected(), Some(0));
+    }
+}
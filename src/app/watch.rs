@@ -0,0 +1,94 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use super::state::App;
+use crate::fs::watch::affects_listing;
+
+impl App {
+    // Point the watcher at the directories the Current panel lists and at
+    // the directory of the previewed document. Called after every input,
+    // so it must be cheap when nothing changed.
+    pub fn sync_watches(&mut self) {
+        let mut wanted = self.listed_dirs();
+        // Watch the document's directory rather than the file itself:
+        // editors often save by renaming a new file over the old one
+        if let Some(dir) = self.previewed_path().and_then(|path| path.parent().map(PathBuf::from)) {
+            wanted.insert(dir);
+        }
+        if let Some(watcher) = &mut self.watcher {
+            watcher.set_watched(&wanted);
+        }
+    }
+
+    // The directories whose entries the Current panel lists: the current
+    // one and the folders open in it
+    fn listed_dirs(&self) -> HashSet<PathBuf> {
+        let mut dirs = HashSet::from([self.current_path.clone()]);
+        let mut entries = self.current_entries.iter().peekable();
+        while let Some(tree_entry) = entries.next() {
+            if entries.peek().is_some_and(|next| next.depth > tree_entry.depth) {
+                dirs.insert(tree_entry.entry.path());
+            }
+        }
+        dirs
+    }
+
+    // Apply changes reported by the watcher. Returns true if anything was
+    // refreshed.
+    pub fn poll_watcher(&mut self) -> bool {
+        let Some(watcher) = &self.watcher else {
+            return false;
+        };
+        let changed = watcher.changed_paths();
+        if changed.is_empty() {
+            return false;
+        }
+
+        let listing = affects_listing(&changed, &self.listed_dirs(), &self.list_options);
+        let document = self
+        .previewed_path()
+        .is_some_and(|document| changed.contains(&document));
+
+        if listing {
+            self.refresh_panels();
+        } else if document {
+            self.reload_preview();
+        }
+        listing || document
+    }
+
+    // Re-read the panels after the directory changed on disk, keeping the
+    // selection, the tree view's folds and the preview's scroll position
+    pub fn refresh_panels(&mut self) {
+        let selected = self.get_selected_entry().map(|entry| entry.path());
+        let previewed = self.previewed_path();
+        let offset = self.preview_scroll_offset;
+
+        self.load_current_entries(true);
+        self.load_parent_entries();
+        // If the selected entry is gone, the index was kept in range
+        if let Some(path) = selected {
+            if let Some(index) = self.entry_index(&path) {
+                self.current_selected.select(Some(index));
+            } else if self.is_loading_tree() {
+                self.pending_selection = Some((path, Some(offset)));
+            }
+        }
+
+        self.reload_preview();
+        if self.previewed_path() != previewed {
+            // A different file took the selection's place
+            self.preview_scroll_offset = 0;
+        } else {
+            self.preview_scroll_offset = offset;
+        }
+    }
+
+    pub fn entry_index(&self, path: &Path) -> Option<usize> {
+        self.current_entries
+        .iter()
+        .position(|tree_entry| tree_entry.entry.path() == path)
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    env, fs,
+    io::{self, BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crossterm::terminal;
+
+use crate::app::state::StartOptions;
+use crate::config::{Config, DEFAULT_CONFIG, ViewMode};
+use crate::fs::export::{default_output, export_file, export_tree, files_exported};
+use crate::fs::{ListOptions, is_markdown_file};
+use crate::ui::html::markdown_to_html;
+use crate::ui::ansi::write_lines;
+use crate::ui::markdown::MarkdownDocument;
+use crate::ui::theme::Theme;
+
+// Width used by `render` when it is not given and stdout is not a terminal
+const DEFAULT_RENDER_WIDTH: u16 = 80;
+
+// ## Command Line ##
+// Doc comments on these fields are the `--help` text.
+
+/// Browse directories and preview Markdown files in the terminal.
+#[derive(Parser, Debug)]
+#[command(name = "greenmerald", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Directory to browse, a Markdown file to open, or `-` to read
+    /// Markdown from standard input (defaults to the current directory)
+    pub path: Option<PathBuf>,
+
+    /// Start in the recursive tree view
+    #[arg(short, long, conflicts_with = "flat")]
+    pub recursive: bool,
+
+    /// Start in the flat view, whatever the config file says
+    #[arg(short, long)]
+    pub flat: bool,
+
+    /// Show hidden files (names starting with '.')
+    #[arg(short = 'a', long)]
+    pub hidden: bool,
+
+    /// Show files that .gitignore, .ignore and git's excludes leave out
+    #[arg(short = 'I', long)]
+    pub no_ignore: bool,
+
+    /// Colour theme: dark, light, gruvbox or a user theme
+    #[arg(short, long, value_name = "NAME", global = true)]
+    pub theme: Option<String>,
+
+    /// Read settings from FILE instead of the default location
+    #[arg(short, long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print a Markdown file to stdout, styled like the preview pane
+    Render(RenderArgs),
+    /// Page through a Markdown file full-screen, like `less`
+    View(ViewArgs),
+    /// Convert a Markdown file, or every Markdown file under a directory, to
+    /// standalone HTML styled like the preview
+    Export(ExportArgs),
+    /// Print the default configuration file, with every setting explained
+    Config(ConfigArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ViewArgs {
+    /// Markdown file to view, or `-` for standard input (the default when
+    /// input is piped)
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Markdown file to render, or `-` for standard input
+    pub file: PathBuf,
+
+    /// Wrap lines to this many columns (defaults to the terminal width)
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub width: Option<u16>,
+
+    /// When to use colours; `auto` colours only when stdout is a terminal
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Markdown file or directory to export, or `-` for standard input
+    pub path: PathBuf,
+
+    /// Where to write the page (default: FILE.html beside the file) or, for
+    /// a directory, the pages and index.html (default: DIR-html beside it).
+    /// Standard input is written to stdout unless this is given.
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Include hidden files and directories when exporting a directory
+    #[arg(short = 'a', long)]
+    pub hidden: bool,
+
+    /// Include files that .gitignore, .ignore and git's excludes leave out
+    #[arg(short = 'I', long)]
+    pub no_ignore: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Save the default configuration as the config file instead of
+    /// printing it (an existing file is left alone)
+    #[arg(long)]
+    pub write: bool,
+
+    /// Print where the config file is read from
+    #[arg(long, conflicts_with = "write")]
+    pub path: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Never,
+    Always,
+    Auto,
+}
+
+impl Cli {
+    // The config file given with --config, or the one in the default
+    // location. Its markdown extensions must be applied before anything
+    // looks at files.
+    pub fn load_config(&self) -> Result<Config, String> {
+        Config::load(self.config.as_deref())
+    }
+
+    // Check the arguments and turn them into the app's start-up options.
+    // Flags on the command line win over the config file.
+    pub fn start_options(&self, config: &Config) -> Result<StartOptions, String> {
+        let mut options = StartOptions::default();
+
+        if let Some(path) = &self.path
+            && is_stdin(path)
+        {
+            options.stdin = Some(read_stdin()?);
+        } else if let Some(path) = &self.path {
+            let path = path
+            .canonicalize()
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+            if path.is_dir() {
+                options.path = path;
+            } else {
+                // A file: browse its directory with the file selected
+                if !is_markdown_file(&path) {
+                    return Err(format!("{}: not a directory or Markdown file", path.display()));
+                }
+                options.path = path.parent().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/"));
+                options.select = Some(path);
+            }
+        }
+
+        options.recursive = self.recursive || (!self.flat && config.general.view == ViewMode::Tree);
+        options.list_options = ListOptions {
+            show_hidden: self.hidden || config.general.show_hidden,
+            show_ignored: self.no_ignore || config.general.show_ignored,
+            sort: config.sort,
+        };
+
+        options.theme = self.theme(config)?;
+        options.config = config.clone();
+        Ok(options)
+    }
+
+    // `greenmerald view`: the start-up options for paging through one file
+    pub fn view_options(&self, args: &ViewArgs, config: &Config) -> Result<StartOptions, String> {
+        let mut options = self.start_options(config)?;
+        let file = match &args.file {
+            Some(file) if !is_stdin(file) => file,
+            None if io::stdin().is_terminal() => {
+                return Err("no file given and nothing piped to standard input".to_string());
+            }
+            _ => {
+                options.stdin = Some(read_stdin()?);
+                return Ok(options);
+            }
+        };
+        let file = file
+        .canonicalize()
+        .map_err(|e| format!("{}: {e}", file.display()))?;
+        if !file.is_file() || !is_markdown_file(&file) {
+            return Err(format!("{}: not a Markdown file", file.display()));
+        }
+        // The (hidden) panels show the file's directory, as for
+        // `greenmerald FILE`
+        options.path = file.parent().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/"));
+        options.select = Some(file.clone());
+        options.pager = Some(file);
+        Ok(options)
+    }
+
+    // `greenmerald config`: show the documented defaults, or save them as a
+    // starting point
+    pub fn config_command(&self, args: &ConfigArgs) -> Result<(), String> {
+        let path = self.config.clone().or_else(Config::default_path);
+        if !args.path && !args.write {
+            print!("{DEFAULT_CONFIG}");
+            return Ok(());
+        }
+        let Some(path) = path else {
+            return Err("no config location: set XDG_CONFIG_HOME or HOME, or use --config".to_string());
+        };
+        if args.path {
+            println!("{}", path.display());
+            return Ok(());
+        }
+        if path.exists() {
+            return Err(format!("{}: already exists, not overwriting it", path.display()));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {e}", parent.display()))?;
+        }
+        fs::write(&path, DEFAULT_CONFIG).map_err(|e| format!("{}: {e}", path.display()))?;
+        println!("Wrote {}", path.display());
+        Ok(())
+    }
+
+    // The theme named with --theme, or else in the config file (which has
+    // already been checked)
+    fn theme(&self, config: &Config) -> Result<Theme, String> {
+        let name = self.theme.as_deref().unwrap_or(&config.general.theme);
+        Ok(Theme::load(name)?.with_colors(&config.colors))
+    }
+
+    // `greenmerald export`: write HTML pages and report where they went
+    pub fn export(&self, args: &ExportArgs, config: &Config) -> Result<(), String> {
+        let theme = self.theme(config)?;
+
+        if is_stdin(&args.path) {
+            let page = markdown_to_html(&read_stdin()?, "Untitled", &theme, false);
+            return match &args.output {
+                Some(output) => fs::write(output, page).map_err(|e| format!("{}: {e}", output.display())),
+                None => io::stdout().write_all(page.as_bytes()).map_err(|e| e.to_string()),
+            };
+        }
+
+        let path = args
+        .path
+        .canonicalize()
+        .map_err(|e| format!("{}: {e}", args.path.display()))?;
+        let output = args.output.clone().unwrap_or_else(|| default_output(&path));
+        if path.is_dir() {
+            let options = ListOptions {
+                show_hidden: args.hidden || config.general.show_hidden,
+                show_ignored: args.no_ignore || config.general.show_ignored,
+                sort: config.sort,
+            };
+            // Nothing cancels an export from the command line
+            let count = export_tree(&path, &output, &theme, &options, &AtomicBool::new(false), |_| {})
+            .map_err(|e| e.to_string())?;
+            println!("Exported {} to {}", files_exported(count), output.display());
+        } else if is_markdown_file(&path) {
+            export_file(&path, &output, &theme, false).map_err(|e| e.to_string())?;
+            println!("Exported to {}", output.display());
+        } else {
+            return Err(format!("{}: not a directory or Markdown file", path.display()));
+        }
+        Ok(())
+    }
+
+    // `greenmerald render`: lay the file out exactly as the preview pane
+    // would and write it to stdout
+    pub fn render(&self, args: &RenderArgs, config: &Config) -> Result<(), String> {
+        let theme = self.theme(config)?;
+        let source = if is_stdin(&args.file) {
+            read_stdin()?
+        } else {
+            fs::read_to_string(&args.file)
+            .map_err(|e| format!("{}: {e}", args.file.display()))?
+        };
+
+        let stdout = io::stdout();
+        let is_terminal = stdout.is_terminal();
+        let color = match args.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // https://no-color.org
+            ColorChoice::Auto => is_terminal && env::var_os("NO_COLOR").is_none(),
+        };
+        let width = args.width.unwrap_or_else(|| {
+            let terminal_width = terminal::size().ok().map(|(columns, _)| columns);
+            terminal_width.filter(|_| is_terminal).unwrap_or(DEFAULT_RENDER_WIDTH)
+        });
+
+        let mut doc = MarkdownDocument::new(source, &theme);
+        doc.rewrap(width);
+
+        let mut out = BufWriter::new(stdout.lock());
+        let result = write_lines(&mut out, &doc.text().lines, color).and_then(|_| out.flush());
+        match result {
+            // The reader went away (e.g. `| head`); that is not an error
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result.map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// `-` stands for standard input, as for most command line tools
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+// Read piped Markdown. Keys are still read from the terminal afterwards, as
+// crossterm opens /dev/tty itself when stdin is not one.
+fn read_stdin() -> Result<String, String> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Err("'-' reads Markdown from a pipe, but standard input is a terminal".to_string());
+    }
+    let mut source = String::new();
+    stdin
+    .read_to_string(&mut source)
+    .map_err(|e| format!("standard input: {e}"))?;
+    Ok(source)
+}
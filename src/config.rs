@@ -0,0 +1,360 @@
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::app::keymap::Keymap;
+use crate::fs::sort::SortOptions;
+use crate::ui::theme::Theme;
+
+// ## Configuration ##
+// Settings read from `config.toml` in the XDG config directory (or the file
+// given with `--config`). Every key is optional; anything left out keeps the
+// value from `DEFAULT_CONFIG`.
+
+// The documented default configuration, printed by `greenmerald config`
+pub const DEFAULT_CONFIG: &str = r##"# Greenmerald configuration
+#
+# Every setting is optional: delete the ones you don't want to change.
+# Colours are names ("blue", "lightcyan", "darkgray"...), "#rrggbb" or a
+# terminal palette index ("208").
+
+[general]
+# View the browser starts in: "flat" or "tree" (`-r` / `-f` override this)
+view = "flat"
+# List files whose name starts with a dot (`-a` turns this on, '.' toggles it)
+show_hidden = false
+# List files that .gitignore, .ignore and git's global excludes leave out
+# (`-I` turns this on, 'I' toggles it)
+show_ignored = false
+# Colour theme: "dark", "light", "gruvbox" or the name of a theme file in
+# the `themes` directory beside this file (`-t` overrides this, 'T' cycles
+# through them all)
+theme = "dark"
+# How often to check for changes while no keys are pressed, in milliseconds
+poll_interval_ms = 250
+
+[layout]
+# Widths of the three panels, in percent of the terminal. They must add up
+# to 100.
+parent = 25
+current = 40
+preview = 35
+# Width of the outline pane ('o'). It takes its room from the Parent and
+# Current panels, so the preview keeps its width.
+outline = 20
+
+[colors]
+# Override a few of the theme's colours, whichever theme is in use
+#
+# The path bar at the top and the pager's status line
+# header_fg = "white"
+# header_bg = "blue"
+# The selected entry in the lists
+# selection_fg = "black"
+# selection_bg = "lightblue"
+# Entries in the Parent panel
+# parent_fg = "darkgray"
+
+[sort]
+# Order of directory listings: "natural" (file2 before file10, ignoring
+# case), "name" (ignoring case), "modified", "size" or "extension" ('s'
+# cycles through them)
+by = "natural"
+# Descending: last, newest or largest first ('S' toggles it)
+reverse = false
+# List directories above files rather than mixed in with them ('D' toggles
+# it)
+dirs_first = true
+
+[tree]
+# How many levels below the current directory the tree view shows. "Expand
+# all" ('zR') opens everything down to it, and neither reopened folders nor
+# 't' go deeper.
+max_depth = 16
+# Most entries the tree view lists by itself when reopening folders or
+# expanding all, and the Ctrl+p finder indexes. Directories past it stay
+# folded until opened with 't'.
+max_entries = 50000
+# Remember which directories of the tree view were open from one session to
+# the next, in $XDG_STATE_HOME/greenmerald/folds.toml
+remember_folds = false
+
+[markdown]
+# File extensions previewed, searched and exported as Markdown
+extensions = ["md", "markdown"]
+
+[keys]
+# Rebind an action by giving its new keys; they replace its default keys and
+# are taken away from any other action. `[]` leaves an action unbound. Press
+# '?' in the app to see every action's name and keys.
+#
+# Keys are written like "j", "G", "ctrl+d", "alt+left", "shift+tab",
+# "space", "enter", "esc", "pgdn" or "f5" ("C-d", "M-left" and "S-Tab"
+# work too). Separate the keys of a sequence
+# with spaces ("ctrl+x e"); a run of plain characters like "gg" is a
+# sequence too.
+#
+# select_next = ["j", "down", "ctrl+n"]
+# scroll_to_top = "gg"
+# toggle_outline = []
+"##;
+
+// Limits for `general.poll_interval_ms`
+const MIN_POLL_INTERVAL_MS: u64 = 10;
+const MAX_POLL_INTERVAL_MS: u64 = 10_000;
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub general: GeneralConfig,
+    pub layout: LayoutConfig,
+    pub colors: ColorConfig,
+    pub sort: SortOptions,
+    pub tree: TreeConfig,
+    pub markdown: MarkdownConfig,
+    // Action name -> keys, applied over the default keymap
+    pub keys: BTreeMap<String, KeyList>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneralConfig {
+    pub view: ViewMode,
+    pub show_hidden: bool,
+    pub show_ignored: bool,
+    pub theme: String,
+    pub poll_interval_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    Flat,
+    Tree,
+}
+
+// Panel widths in percent
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub parent: u16,
+    pub current: u16,
+    pub preview: u16,
+    pub outline: u16,
+}
+
+// Unset colours come from the theme
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    #[serde(deserialize_with = "optional_color")]
+    pub header_fg: Option<Color>,
+    #[serde(deserialize_with = "optional_color")]
+    pub header_bg: Option<Color>,
+    #[serde(deserialize_with = "optional_color")]
+    pub selection_fg: Option<Color>,
+    #[serde(deserialize_with = "optional_color")]
+    pub selection_bg: Option<Color>,
+    #[serde(deserialize_with = "optional_color")]
+    pub parent_fg: Option<Color>,
+}
+
+// Limits on what the recursive view lists before it stops opening
+// directories
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TreeConfig {
+    // Levels opened, counting the current directory's entries as 1
+    pub max_depth: usize,
+    pub max_entries: usize,
+    pub remember_folds: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownConfig {
+    // Without the leading dot, compared case-insensitively
+    pub extensions: Vec<String>,
+}
+
+// The keys for one action: a single binding or a list of them
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn specs(&self) -> &[String] {
+        match self {
+            KeyList::One(spec) => std::slice::from_ref(spec),
+            KeyList::Many(specs) => specs,
+        }
+    }
+}
+
+// The values written out in `DEFAULT_CONFIG`; the test below checks the
+// two agree
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        GeneralConfig {
+            view: ViewMode::Flat,
+            show_hidden: false,
+            show_ignored: false,
+            theme: "dark".to_string(),
+            poll_interval_ms: 250,
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            parent: 25,
+            current: 40,
+            preview: 35,
+            outline: 20,
+        }
+    }
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            max_depth: 16,
+            max_entries: 50000,
+            remember_folds: false,
+        }
+    }
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        MarkdownConfig {
+            extensions: vec!["md".to_string(), "markdown".to_string()],
+        }
+    }
+}
+
+impl Config {
+    // Where the config file is looked for: $XDG_CONFIG_HOME/greenmerald/
+    // config.toml, or ~/.config/greenmerald/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("greenmerald").join("config.toml"))
+    }
+
+    // User themes: $XDG_CONFIG_HOME/greenmerald/themes/NAME.toml
+    pub fn themes_dir() -> Option<PathBuf> {
+        Config::default_path().and_then(|path| Some(path.parent()?.join("themes")))
+    }
+
+    // Load the file given on the command line, which must exist, or the
+    // one in the default location if there is one
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(Config::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        Config::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.validate()?;
+        // Accept ".md" as well as "md"
+        for extension in &mut config.markdown.extensions {
+            *extension = extension.trim_start_matches('.').to_string();
+        }
+        config.markdown.extensions.retain(|extension| !extension.is_empty());
+        Ok(config)
+    }
+
+    // Check the values that parse but make no sense
+    fn validate(&self) -> Result<(), String> {
+        let layout = &self.layout;
+        let total = layout.parent as u32 + layout.current as u32 + layout.preview as u32;
+        if total != 100 {
+            return Err(format!(
+                "layout: parent + current + preview must add up to 100, not {total}"
+            ));
+        }
+        if layout.preview == 0 {
+            return Err("layout: preview must be wider than 0".to_string());
+        }
+        // Each is at most 100 here, so the sum cannot overflow
+        if layout.outline >= layout.parent + layout.current {
+            return Err(format!(
+                "layout: outline must be narrower than parent + current ({})",
+                layout.parent + layout.current
+            ));
+        }
+
+        let interval = self.general.poll_interval_ms;
+        if !(MIN_POLL_INTERVAL_MS..=MAX_POLL_INTERVAL_MS).contains(&interval) {
+            return Err(format!(
+                "general: poll_interval_ms must be between {MIN_POLL_INTERVAL_MS} and \
+                 {MAX_POLL_INTERVAL_MS}, not {interval}"
+            ));
+        }
+
+        if self.tree.max_depth == 0 {
+            return Err("tree: max_depth must be at least 1".to_string());
+        }
+        if self.tree.max_entries == 0 {
+            return Err("tree: max_entries must be at least 1".to_string());
+        }
+
+        Theme::load(&self.general.theme).map_err(|e| format!("general: {e}"))?;
+
+        if self.markdown.extensions.iter().all(|extension| extension.trim_start_matches('.').is_empty()) {
+            return Err("markdown: extensions must list at least one extension".to_string());
+        }
+
+        Keymap::new(&self.keys).map_err(|e| format!("keys: {e}"))?;
+        Ok(())
+    }
+}
+
+// Colours are written as names ("blue"), "#rrggbb" or palette indexes
+pub fn parse_color(name: &str) -> Result<Color, String> {
+    Color::from_str(name).map_err(|_| {
+        format!("unknown colour '{name}' (use a name like \"blue\", \"#rrggbb\" or a palette index)")
+    })
+}
+
+// Say what went wrong with a colour in terms of the file
+pub fn optional_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_color(&name).map(Some).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `greenmerald config` prints DEFAULT_CONFIG as the defaults, so it must
+    // not drift from what an empty file gives
+    #[test]
+    fn default_config_matches_the_defaults() {
+        assert_eq!(Config::parse(DEFAULT_CONFIG), Ok(Config::default()));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    ops::ControlFlow,
+    path::{self, Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread,
+};
+
+use super::{ListOptions, is_dir_entry, is_markdown_file, tree::walk_tree};
+use crate::ui::html::markdown_to_html;
+use crate::ui::theme::Theme;
+
+// Page listing every exported file, written unless the tree has its own
+// index.md (which is exported to the same name)
+const INDEX_PAGE: &str = "index.html";
+
+// Where an export goes when no output is given: `notes/todo.md` becomes
+// `notes/todo.html` and the `notes/` tree goes to `notes-html/` beside it
+pub fn default_output(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        let mut name = path.file_name().map_or_else(|| OsString::from("export"), OsString::from);
+        name.push("-html");
+        path.with_file_name(name)
+    } else {
+        path.with_extension("html")
+    }
+}
+
+// Convert one Markdown file to a standalone HTML page. Links to other
+// Markdown files are only pointed at their pages when `rewrite_links` says
+// those are exported too.
+pub fn export_file(path: &Path, output: &Path, theme: &Theme, rewrite_links: bool) -> io::Result<()> {
+    let source = fs::read_to_string(path).map_err(|e| with_path(e, path))?;
+    let title = path.file_stem().unwrap_or_default().to_string_lossy();
+    let page = markdown_to_html(&source, &title, theme, rewrite_links);
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| with_path(e, parent))?;
+    }
+    fs::write(output, page).map_err(|e| with_path(e, output))
+}
+
+// Export every Markdown file under `root` to the same place under
+// `output`, plus an index page linking to them all. Files are exported as
+// the walk comes to them, calling `exported` with the count so far, and the
+// walk stops once `cancel` is set. Returns the number of files exported.
+pub fn export_tree(
+    root: &Path,
+    output: &Path,
+    theme: &Theme,
+    options: &ListOptions,
+    cancel: &AtomicBool,
+    mut exported: impl FnMut(usize),
+) -> io::Result<usize> {
+    let root = path::absolute(root)?;
+    let output = path::absolute(output)?;
+
+    // (path relative to root, depth, is a directory), skipping an earlier
+    // export that was written inside the tree
+    let mut entries: Vec<(PathBuf, usize, bool)> = Vec::new();
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut failed = None;
+    walk_tree(&root, options, cancel, |entry, depth| {
+        if cancel.load(Ordering::Relaxed) {
+            return ControlFlow::Break(());
+        }
+        let path = entry.path();
+        if path.starts_with(&output) {
+            return ControlFlow::Continue(());
+        }
+        let Ok(relative) = path.strip_prefix(&root).map(Path::to_path_buf) else {
+            return ControlFlow::Continue(());
+        };
+        let is_dir = is_dir_entry(&entry);
+        if !is_dir && is_markdown_file(&relative) {
+            if let Err(e) = export_file(&path, &output.join(&relative).with_extension("html"), theme, true) {
+                failed = Some(e);
+                return ControlFlow::Break(());
+            }
+            files.push(relative.clone());
+            exported(files.len());
+        }
+        entries.push((relative, depth, is_dir));
+        ControlFlow::Continue(())
+    })
+    .map_err(|e| with_path(e, &root))?;
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    if cancel.load(Ordering::Relaxed) {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "export cancelled"));
+    }
+
+    if !root.join("index.md").is_file() {
+        // Directories are only listed if something below them was exported
+        let title = root.file_name().unwrap_or_default().to_string_lossy();
+        let mut index = format!("# {}\n\n", escape_markdown(&title));
+        for (path, depth, is_dir) in &entries {
+            let name = escape_markdown(&path.file_name().unwrap_or_default().to_string_lossy());
+            let indent = "  ".repeat(*depth);
+            if *is_dir && files.iter().any(|file| file.starts_with(path)) {
+                index.push_str(&format!("{indent}- {name}/\n"));
+            } else if files.contains(path) {
+                // Written as a link to the .md file, which the export
+                // rewrites like any other link
+                index.push_str(&format!("{indent}- [{name}](<{}>)\n", path.to_string_lossy()));
+            }
+        }
+        fs::create_dir_all(&output).map_err(|e| with_path(e, &output))?;
+        let page = markdown_to_html(&index, "Index", theme, true);
+        let index_path = output.join(INDEX_PAGE);
+        fs::write(&index_path, page).map_err(|e| with_path(e, &index_path))?;
+    }
+    Ok(files.len())
+}
+
+// What the export thread sends back, in order
+pub enum ExportMessage {
+    // Another file was written; the number written so far
+    Progress(usize),
+    // The export finished or failed
+    Done(io::Result<usize>),
+}
+
+// A directory export running on a background thread. Dropping it cancels
+// the export.
+pub struct ExportHandle {
+    pub receiver: Receiver<ExportMessage>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for ExportHandle {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Run `export_tree` on a new thread, reporting each file written through
+// the handle's receiver
+pub fn spawn_export_tree(root: &Path, output: &Path, theme: Theme, options: ListOptions) -> ExportHandle {
+    let (sender, receiver) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let worker_cancel = Arc::clone(&cancel);
+    let (root, output) = (root.to_path_buf(), output.to_path_buf());
+    thread::spawn(move || {
+        let result = export_tree(&root, &output, &theme, &options, &worker_cancel, |count| {
+            let _ = sender.send(ExportMessage::Progress(count));
+        });
+        // The receiver is gone if the export was cancelled
+        let _ = sender.send(ExportMessage::Done(result));
+    });
+    ExportHandle { receiver, cancel }
+}
+
+// "1 file" / "3 files", for reporting what `export_tree` did
+pub fn files_exported(count: usize) -> String {
+    if count == 1 {
+        "1 file".to_string()
+    } else {
+        format!("{count} files")
+    }
+}
+
+// Backslash-escape the characters Markdown would treat as syntax in a name
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// io errors don't say which file they are about
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {error}", path.display()))
+}
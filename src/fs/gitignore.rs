@@ -0,0 +1,181 @@
+use std::{path::Path, sync::Arc};
+
+use ignore::{
+    Match,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+
+// ## Ignore Rules ##
+// What `.gitignore`, `.ignore`, `.git/info/exclude` and git's global
+// excludes file say about the entries of a directory, following git: the
+// deepest file with a matching pattern decides, and a `!pattern` can bring
+// back what a file higher up ignored. `.gitignore` files only count inside
+// a git repository; `.ignore` files count anywhere.
+
+// The rules that apply to one directory's entries
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    // Lowest precedence first
+    matchers: Vec<Arc<Gitignore>>,
+    // Whether the directory is inside a git repository
+    in_repo: bool,
+}
+
+impl IgnoreRules {
+    // Gather the rules for `dir` from it and every directory above it
+    pub fn for_dir(dir: &Path) -> IgnoreRules {
+        let ancestors: Vec<&Path> = dir.ancestors().collect();
+        let repo_root = ancestors.iter().position(|ancestor| ancestor.join(".git").exists());
+
+        let mut rules = IgnoreRules::default();
+        if let Some(index) = repo_root {
+            let root = ancestors[index];
+            rules.in_repo = true;
+            let (global, _) = GitignoreBuilder::new(root).build_global();
+            rules.push(global);
+            rules.push_file(root, &root.join(".git").join("info").join("exclude"));
+        }
+        // From the top down, so deeper files take precedence
+        for (index, ancestor) in ancestors.iter().enumerate().rev() {
+            if repo_root.is_some_and(|root| index <= root) {
+                rules.push_file(ancestor, &ancestor.join(".gitignore"));
+            }
+            rules.push_file(ancestor, &ancestor.join(".ignore"));
+        }
+        rules
+    }
+
+    // The rules for a subdirectory: these plus the subdirectory's own files
+    pub fn child(&self, dir: &Path) -> IgnoreRules {
+        let mut rules = self.clone();
+        // A nested repository starts its own set of rules, as in git
+        if dir.join(".git").exists() {
+            return IgnoreRules::for_dir(dir);
+        }
+        if rules.in_repo {
+            rules.push_file(dir, &dir.join(".gitignore"));
+        }
+        rules.push_file(dir, &dir.join(".ignore"));
+        rules
+    }
+
+    // Whether an entry of the directory is ignored. The repository's own
+    // `.git` directory always is.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        for matcher in self.matchers.iter().rev() {
+            match matcher.matched_path_or_any_parents(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+
+    // Add the patterns of an ignore file in `dir`, if there is one.
+    // Patterns that don't parse are skipped, as git does.
+    fn push_file(&mut self, dir: &Path, file: &Path) {
+        if !file.is_file() {
+            return;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        builder.add(file);
+        if let Ok(matcher) = builder.build() {
+            self.push(matcher);
+        }
+    }
+
+    fn push(&mut self, matcher: Gitignore) {
+        if !matcher.is_empty() {
+            self.matchers.push(Arc::new(matcher));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    // A fresh directory holding the given files, removed again when dropped
+    struct TempTree(PathBuf);
+
+    impl TempTree {
+        fn new(name: &str, files: &[(&str, &str)]) -> TempTree {
+            let root = std::env::temp_dir().join(format!("greenmerald-ignore-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            for (file, text) in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, text).unwrap();
+            }
+            TempTree(root)
+        }
+
+        fn ignores(&self, rules: &IgnoreRules, file: &str) -> bool {
+            rules.is_ignored(&self.0.join(file), false)
+        }
+    }
+
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn gitignore_counts_only_inside_a_repository() {
+        let plain = TempTree::new("plain", &[(".gitignore", "*.log\n")]);
+        assert!(!plain.ignores(&IgnoreRules::for_dir(&plain.0), "build.log"));
+
+        let repo = TempTree::new("repo", &[(".git/HEAD", ""), (".gitignore", "*.log\n")]);
+        let rules = IgnoreRules::for_dir(&repo.0);
+        assert!(repo.ignores(&rules, "build.log"));
+        assert!(!repo.ignores(&rules, "notes.md"));
+        // The repository's own metadata is never listed
+        assert!(rules.is_ignored(&repo.0.join(".git"), true));
+    }
+
+    #[test]
+    fn ignore_files_count_anywhere() {
+        let plain = TempTree::new("dot-ignore", &[(".ignore", "draft.md\n"), ("sub/.ignore", "scratch/\n")]);
+        let rules = IgnoreRules::for_dir(&plain.0);
+        assert!(plain.ignores(&rules, "draft.md"));
+        // Below the directory they are in too, with the subdirectory's own
+        let sub = rules.child(&plain.0.join("sub"));
+        assert!(plain.ignores(&sub, "sub/draft.md"));
+        assert!(sub.is_ignored(&plain.0.join("sub/scratch"), true));
+        assert!(!plain.ignores(&sub, "sub/kept.md"));
+    }
+
+    #[test]
+    fn info_exclude_is_honoured() {
+        let repo = TempTree::new("exclude", &[(".git/info/exclude", "secret.md\n"), ("sub/x.md", "")]);
+        let rules = IgnoreRules::for_dir(&repo.0);
+        assert!(repo.ignores(&rules, "secret.md"));
+        assert!(repo.ignores(&rules.child(&repo.0.join("sub")), "sub/secret.md"));
+        assert!(repo.ignores(&IgnoreRules::for_dir(&repo.0.join("sub")), "sub/secret.md"));
+    }
+
+    #[test]
+    fn negated_patterns_bring_files_back() {
+        let repo = TempTree::new(
+            "negate",
+            &[(".git/HEAD", ""), (".gitignore", "*.md\n!keep.md\n"), ("sub/.gitignore", "!also.md\n")],
+        );
+        let rules = IgnoreRules::for_dir(&repo.0);
+        assert!(repo.ignores(&rules, "other.md"));
+        assert!(!repo.ignores(&rules, "keep.md"));
+
+        // A deeper file overrides the one above it
+        let sub = rules.child(&repo.0.join("sub"));
+        assert!(!repo.ignores(&sub, "sub/also.md"));
+        assert!(!repo.ignores(&sub, "sub/keep.md"));
+        assert!(repo.ignores(&sub, "sub/other.md"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+};
+
+use regex::{Regex, RegexBuilder};
+
+use super::{ListOptions, is_markdown_file, tree::walk_tree};
+
+// Stop collecting after this many hits; a query like "e" would otherwise
+// flood the results list
+pub const MAX_HITS: usize = 5000;
+// Longest snippet kept per hit, in characters
+const MAX_SNIPPET: usize = 200;
+
+// A line of a Markdown file that matched the query
+pub struct GrepHit {
+    pub path: PathBuf,
+    // 0-based line number in the file
+    pub line: usize,
+    // The matching line, trimmed
+    pub snippet: String,
+}
+
+// What the search thread sends back, in order
+pub enum GrepMessage {
+    Hit(GrepHit),
+    // The walk finished (or was cut short by MAX_HITS)
+    Done { files: usize, truncated: bool },
+}
+
+// A search running on a background thread. Dropping it cancels the search.
+pub struct GrepHandle {
+    pub receiver: Receiver<GrepMessage>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for GrepHandle {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Queries are matched as literal text, ignoring case
+fn query_regex(query: &str) -> Regex {
+    RegexBuilder::new(&regex::escape(query))
+    .case_insensitive(true)
+    .build()
+    .expect("escaped query is a valid regex")
+}
+
+// Search every Markdown file under `root` for `query` on a new thread.
+// Hits are streamed through the handle's receiver as they are found.
+pub fn spawn_grep(root: &Path, query: &str, options: ListOptions) -> GrepHandle {
+    let (sender, receiver) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let root = root.to_path_buf();
+    let regex = query_regex(query);
+    let worker_cancel = Arc::clone(&cancel);
+    thread::spawn(move || grep_tree(&root, &regex, &options, &sender, &worker_cancel));
+    GrepHandle { receiver, cancel }
+}
+
+// Files are searched as the walk comes to them, so the first hits show up
+// right away however big the tree is
+fn grep_tree(
+    root: &Path,
+    regex: &Regex,
+    options: &ListOptions,
+    sender: &Sender<GrepMessage>,
+    cancel: &AtomicBool,
+) {
+    let (mut files, mut hits) = (0, 0);
+    let mut truncated = false;
+    // Unreadable directories are skipped, like in the tree view
+    let _ = walk_tree(root, options, cancel, |entry, _| {
+        let path = entry.path();
+        if !is_markdown_file(&path) || !path.is_file() {
+            return ControlFlow::Continue(());
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            return ControlFlow::Continue(());
+        };
+        files += 1;
+        for (line, text) in content.lines().enumerate() {
+            if !regex.is_match(text) {
+                continue;
+            }
+            let hit = GrepHit {
+                path: path.clone(),
+                line,
+                snippet: text.trim().chars().take(MAX_SNIPPET).collect(),
+            };
+            // A failed send means the UI dropped the search
+            if cancel.load(Ordering::Relaxed) || sender.send(GrepMessage::Hit(hit)).is_err() {
+                return ControlFlow::Break(());
+            }
+            hits += 1;
+            if hits >= MAX_HITS {
+                truncated = true;
+                return ControlFlow::Break(());
+            }
+        }
+        if cancel.load(Ordering::Relaxed) {
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    if !cancel.load(Ordering::Relaxed) {
+        let _ = sender.send(GrepMessage::Done { files, truncated });
+    }
+}
@@ -1,57 +1,235 @@
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
     fs, io,
-    path::Path,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread,
 };
-use crate::app::state::TreeEntry;
 use crate::utils;
+use gitignore::IgnoreRules;
+use sort::{SortOptions, sort_entries};
+use tree::walk_tree;
 
-pub fn read_dir_entries(path: &Path) -> io::Result<Vec<fs::DirEntry>> {
+pub mod export;
+pub mod gitignore;
+pub mod grep;
+pub mod sort;
+pub mod tree;
+pub mod watch;
+
+// Extensions used until the configuration says otherwise
+const DEFAULT_MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+// Set once at startup from the `[markdown]` section of the config. The
+// background search and index threads need it too, so it lives here rather
+// than on App.
+static MARKDOWN_EXTENSIONS: OnceLock<Vec<String>> = OnceLock::new();
+
+// Choose which extensions count as Markdown. Only the first call has an
+// effect.
+pub fn set_markdown_extensions(extensions: Vec<String>) {
+    let _ = MARKDOWN_EXTENSIONS.set(extensions);
+}
+
+// Whether a path should be treated as a Markdown document
+pub fn is_markdown_file(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(OsStr::to_str) else {
+        return false;
+    };
+    match MARKDOWN_EXTENSIONS.get() {
+        Some(extensions) => extensions.iter().any(|markdown| ext.eq_ignore_ascii_case(markdown)),
+        None => DEFAULT_MARKDOWN_EXTENSIONS.iter().any(|markdown| ext.eq_ignore_ascii_case(markdown)),
+    }
+}
+
+// Whether an entry is a directory or a symbolic link to one. Only links
+// cost a `stat`; the type of anything else comes with the listing.
+pub fn is_dir_entry(entry: &fs::DirEntry) -> bool {
+    match entry.file_type() {
+        Ok(ft) if ft.is_symlink() => entry.path().is_dir(),
+        Ok(ft) => ft.is_dir(),
+        Err(_) => false,
+    }
+}
+
+// Where a symbolic link points
+pub struct LinkTarget {
+    // As written in the link, so usually relative to its directory
+    pub target: PathBuf,
+    // Nothing is there (or the links go round in a loop)
+    pub broken: bool,
+}
+
+// The target of `path` if it is a symbolic link
+pub fn link_target(path: &Path) -> Option<LinkTarget> {
+    let target = fs::read_link(path).ok()?;
+    Some(LinkTarget {
+        target,
+        broken: !path.exists(),
+    })
+}
+
+// What identifies a directory however it is reached, so walks that follow
+// links can tell when they come back to one: its device and inode
+#[cfg(unix)]
+pub type DirId = (u64, u64);
+// ...or where there are none, its canonical path
+#[cfg(not(unix))]
+pub type DirId = PathBuf;
+
+#[cfg(unix)]
+pub fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn dir_id(path: &Path) -> Option<DirId> {
+    fs::canonicalize(path).ok()
+}
+
+// Which of a directory's subdirectories a walk opens: all the real ones,
+// which can't form a loop, and links to directories not opened yet. Every
+// directory opened is added to `visited`, real ones first, so a link beside
+// its own target doesn't take the target's place.
+fn dirs_to_open(entries: &[fs::DirEntry], visited: &mut HashSet<DirId>) -> HashSet<PathBuf> {
+    let (links, dirs): (Vec<&fs::DirEntry>, Vec<&fs::DirEntry>) = entries
+    .iter()
+    .filter(|entry| is_dir_entry(entry))
+    .partition(|entry| entry.file_type().is_ok_and(|ft| ft.is_symlink()));
+    let mut open = HashSet::new();
+    for entry in dirs {
+        let path = entry.path();
+        if let Some(id) = dir_id(&path) {
+            visited.insert(id);
+        }
+        open.insert(path);
+    }
+    for entry in links {
+        let path = entry.path();
+        if dir_id(&path).is_some_and(|id| visited.insert(id)) {
+            open.insert(path);
+        }
+    }
+    open
+}
+
+// Which entries directory listings include
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListOptions {
+    // Show entries whose name starts with a dot
+    pub show_hidden: bool,
+    // Show entries that .gitignore / .ignore rules exclude
+    pub show_ignored: bool,
+    // The order entries are listed in
+    pub sort: SortOptions,
+}
+
+impl ListOptions {
+    fn includes(&self, entry: &fs::DirEntry, rules: &IgnoreRules) -> bool {
+        let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+        self.includes_path(&entry.path(), is_dir, rules)
+    }
+
+    // Whether a listing of the directory holding `path` would include it
+    fn includes_path(&self, path: &Path, is_dir: bool, rules: &IgnoreRules) -> bool {
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if !self.show_hidden && hidden {
+            return false;
+        }
+        if !self.show_ignored {
+            return !rules.is_ignored(path, is_dir);
+        }
+        true
+    }
+
+    // The ignore rules for listing `dir`, unless ignored files are shown
+    fn rules_for(&self, dir: &Path) -> IgnoreRules {
+        if self.show_ignored {
+            IgnoreRules::default()
+        } else {
+            IgnoreRules::for_dir(dir)
+        }
+    }
+}
+
+// Every path below `root`, as the recursive tree view would list them
+pub struct PathIndex {
+    pub paths: Vec<PathBuf>,
+    // Whether the walk stopped at the entry limit
+    pub truncated: bool,
+}
+
+// An index being built on a background thread. Dropping it cancels the walk.
+pub struct PathIndexHandle {
+    pub receiver: Receiver<PathIndex>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for PathIndexHandle {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Index the paths below `root` on a new thread, up to `max_entries` of
+// them. The index is sent once the walk is done.
+pub fn spawn_path_index(root: &Path, options: ListOptions, max_entries: usize) -> PathIndexHandle {
+    let (sender, receiver) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let worker_cancel = Arc::clone(&cancel);
+    let root = root.to_path_buf();
+    thread::spawn(move || {
+        let mut index = PathIndex { paths: Vec::new(), truncated: false };
+        let _ = walk_tree(&root, &options, &worker_cancel, |entry, _| {
+            if index.paths.len() >= max_entries {
+                index.truncated = true;
+                return ControlFlow::Break(());
+            }
+            index.paths.push(entry.path());
+            ControlFlow::Continue(())
+        });
+        // The receiver is gone if the finder was closed in the meantime
+        let _ = sender.send(index);
+    });
+    PathIndexHandle { receiver, cancel }
+}
+
+pub fn read_dir_entries(path: &Path, options: &ListOptions) -> io::Result<Vec<fs::DirEntry>> {
+       list_dir(path, options, &options.rules_for(path))
+   }
+
+   // `read_dir_entries` with the ignore rules for `path` already gathered
+fn list_dir(path: &Path, options: &ListOptions, rules: &IgnoreRules) -> io::Result<Vec<fs::DirEntry>> {
        let mut entries = fs::read_dir(path)?
        .filter_map(|res| res.ok())
+       .filter(|entry| options.includes(entry, rules))
        .collect::<Vec<_>>();
 
-       // Sort: directories first, then files, alphabetically
-       entries.sort_by_key(|entry| {
-           let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-           (!is_dir, entry.file_name())
-       });
+       sort_entries(&mut entries, &options.sort);
        Ok(entries)
    }
 
-   // NEW: Helper function to recursively build the tree
-pub fn build_recursive_tree(
-       path: &Path,
-       current_depth: usize,
-       entries_list: &mut Vec<TreeEntry>,
-   ) -> io::Result<()> {
-       let entries = read_dir_entries(path)?;
-
-       for entry in entries {
-           let path = entry.path();
-           let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-
-           // Add current entry
-           entries_list.push(TreeEntry {
-               entry, // entry is moved here
-               depth: current_depth,
-           });
-
-           // If it was a directory, recurse
-           if is_dir {
-               // Ignore errors on recursion (e.g., permission denied)
-               let _ = build_recursive_tree(&path, current_depth + 1, entries_list);
-           }
-       }
-       Ok(())
-   }
-
 
     // Gets "relevant information" for the preview panel (UNCHANGED)
     pub fn get_entry_info(entry: &fs::DirEntry) -> String {
+        get_path_info(&entry.path())
+    }
+
+    // Same as `get_entry_info`, for a path that was not listed from a directory
+    pub fn get_path_info(path: &Path) -> String {
         let mut info = String::new();
-        info.push_str(&format!("Name: {}\n", entry.file_name().to_string_lossy()));
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        info.push_str(&format!("Name: {}\n", name.to_string_lossy()));
 
-        if let Ok(metadata) = entry.metadata() {
+        // Like DirEntry::metadata, don't follow symlinks
+        if let Ok(metadata) = fs::symlink_metadata(path) {
             let file_type = if metadata.is_dir() {
                 "Directory"
             } else if metadata.is_file() {
@@ -63,14 +241,19 @@ pub fn build_recursive_tree(
             };
             info.push_str(&format!("Type: {}\n", file_type));
 
+            if let Some(link) = link_target(path) {
+                let broken = if link.broken { " (broken)" } else { "" };
+                info.push_str(&format!("Target: {}{broken}\n", link.target.display()));
+            }
+
             if metadata.is_file() {
                 info.push_str(&format!("Size: {}\n", utils::format_size(metadata.len())));
             }
 
-            if let Ok(modified) = metadata.modified() {
-                if let Ok(duration) = modified.duration_since(std::time::SystemTime::UNIX_EPOCH) {
-                    info.push_str(&format!("Modified (epoch): {}\n", duration.as_secs()));
-                }
+            if let Ok(modified) = metadata.modified()
+                && let Ok(duration) = modified.duration_since(std::time::SystemTime::UNIX_EPOCH)
+            {
+                info.push_str(&format!("Modified (epoch): {}\n", duration.as_secs()));
             }
 
             #[cfg(unix)]
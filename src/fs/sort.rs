@@ -0,0 +1,282 @@
+use std::{
+    cmp::Ordering,
+    fmt, fs,
+    iter::Peekable,
+    str::Chars,
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+use super::is_dir_entry;
+
+// ## Sorting ##
+// The order directory listings come in, chosen in the `[sort]` section of
+// the config and changed on the fly with 's', 'S' and 'D'.
+
+// What entries are compared by. Ties go to the natural order of the names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    // By name, with runs of digits compared as numbers: "file2" before
+    // "file10". Case is ignored.
+    Natural,
+    // By name, ignoring case
+    Name,
+    // Oldest first
+    Modified,
+    // Smallest first
+    Size,
+    // By extension, ignoring case
+    Extension,
+}
+
+impl SortBy {
+    // The next mode 's' switches to
+    pub fn next(self) -> SortBy {
+        match self {
+            SortBy::Natural => SortBy::Name,
+            SortBy::Name => SortBy::Modified,
+            SortBy::Modified => SortBy::Size,
+            SortBy::Size => SortBy::Extension,
+            SortBy::Extension => SortBy::Natural,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortBy::Natural => "natural",
+            SortBy::Name => "name",
+            SortBy::Modified => "modified",
+            SortBy::Size => "size",
+            SortBy::Extension => "extension",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SortOptions {
+    pub by: SortBy,
+    // Descending: largest, newest or last first
+    pub reverse: bool,
+    // Keep directories above files rather than mixing them in
+    pub dirs_first: bool,
+}
+
+// The values written out in `DEFAULT_CONFIG`
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions {
+            by: SortBy::Natural,
+            reverse: false,
+            dirs_first: true,
+        }
+    }
+}
+
+// As shown in the Current panel's title: "natural ↑", "size ↓, mixed"
+impl fmt::Display for SortOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arrow = if self.reverse { '↓' } else { '↑' };
+        write!(f, "{} {arrow}", self.by.name())?;
+        if !self.dirs_first {
+            write!(f, ", mixed")?;
+        }
+        Ok(())
+    }
+}
+
+// What an entry is sorted on, read once per entry. Times and sizes are only
+// looked up when sorting by them.
+struct SortKey {
+    is_dir: bool,
+    name: String,
+    modified: Option<SystemTime>,
+    size: u64,
+    extension: String,
+}
+
+impl SortKey {
+    fn of(entry: &fs::DirEntry, by: SortBy) -> SortKey {
+        let path = entry.path();
+        let is_dir = is_dir_entry(entry);
+        // Links are sorted as what they point to
+        let metadata = if matches!(by, SortBy::Modified | SortBy::Size) {
+            fs::metadata(&path).or_else(|_| entry.metadata()).ok()
+        } else {
+            None
+        };
+        SortKey {
+            is_dir,
+            name: entry.file_name().to_string_lossy().into_owned(),
+            modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            // Directories count as empty, so they gather at one end
+            size: metadata.filter(|_| !is_dir).map_or(0, |metadata| metadata.len()),
+            extension: path
+            .extension()
+            .filter(|_| !is_dir)
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+        }
+    }
+
+    fn compare(&self, other: &SortKey, by: SortBy) -> Ordering {
+        let primary = match by {
+            SortBy::Natural => Ordering::Equal,
+            SortBy::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            SortBy::Modified => self.modified.cmp(&other.modified),
+            SortBy::Size => self.size.cmp(&other.size),
+            SortBy::Extension => self.extension.cmp(&other.extension),
+        };
+        primary
+        .then_with(|| natural_cmp(&self.name, &other.name))
+        // Names that differ only in case still get a fixed order
+        .then_with(|| self.name.cmp(&other.name))
+    }
+}
+
+// Put `entries` in the order `sort` asks for
+pub fn sort_entries(entries: &mut Vec<fs::DirEntry>, sort: &SortOptions) {
+    let mut keyed: Vec<(SortKey, fs::DirEntry)> = entries
+    .drain(..)
+    .map(|entry| (SortKey::of(&entry, sort.by), entry))
+    .collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        let group = if sort.dirs_first {
+            b.is_dir.cmp(&a.is_dir)
+        } else {
+            Ordering::Equal
+        };
+        let order = a.compare(b, sort.by);
+        group.then(if sort.reverse { order.reverse() } else { order })
+    });
+    entries.extend(keyed.into_iter().map(|(_, entry)| entry));
+}
+
+// Compare names as people read them: runs of digits by their value, the
+// rest ignoring case. "File2.md" < "file10.md" < "FILE10b.md".
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let order = compare_numbers(&take_digits(&mut a), &take_digits(&mut b));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+// Two runs of digits by value, however long; "007" equals "7" here and
+// the raw names break the tie later
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // A fresh directory holding the given subdirectories and empty files,
+    // removed again when dropped
+    struct Listing(PathBuf);
+
+    impl Listing {
+        fn new(name: &str, dirs: &[&str], files: &[&str]) -> Listing {
+            let root = std::env::temp_dir().join(format!("greenmerald-sort-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            for dir in dirs {
+                fs::create_dir_all(root.join(dir)).unwrap();
+            }
+            for file in files {
+                fs::write(root.join(file), "").unwrap();
+            }
+            Listing(root)
+        }
+
+        // The names in the order `sort` puts them
+        fn sorted(&self, sort: SortOptions) -> Vec<String> {
+            let mut entries: Vec<fs::DirEntry> = fs::read_dir(&self.0).unwrap().map(Result::unwrap).collect();
+            sort_entries(&mut entries, &sort);
+            entries.iter().map(|entry| entry.file_name().to_string_lossy().into_owned()).collect()
+        }
+    }
+
+    impl Drop for Listing {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn natural(reverse: bool, dirs_first: bool) -> SortOptions {
+        SortOptions { by: SortBy::Natural, reverse, dirs_first }
+    }
+
+    #[test]
+    fn numbers_compare_by_value_ignoring_case() {
+        assert_eq!(natural_cmp("File2.md", "file10.md"), Ordering::Less);
+        assert_eq!(natural_cmp("file10.md", "FILE10b.md"), Ordering::Less);
+        assert_eq!(natural_cmp("file10.md", "File2.md"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_zeros_tie_and_the_raw_name_decides() {
+        assert_eq!(natural_cmp("007", "7"), Ordering::Equal);
+        let listing = Listing::new("zeros", &[], &["7.md", "007.md"]);
+        assert_eq!(listing.sorted(natural(false, true)), ["007.md", "7.md"]);
+    }
+
+    #[test]
+    fn reverse_keeps_directories_first() {
+        let listing = Listing::new("reverse", &["docs", "Zeta"], &["file10.md", "File2.md", "a.md"]);
+        assert_eq!(
+            listing.sorted(natural(false, true)),
+            ["docs", "Zeta", "a.md", "File2.md", "file10.md"]
+        );
+        assert_eq!(
+            listing.sorted(natural(true, true)),
+            ["Zeta", "docs", "file10.md", "File2.md", "a.md"]
+        );
+    }
+
+    #[test]
+    fn mixed_sorts_directories_among_files() {
+        let listing = Listing::new("mixed", &["docs", "Zeta"], &["file10.md", "File2.md", "a.md"]);
+        assert_eq!(
+            listing.sorted(natural(false, false)),
+            ["a.md", "docs", "File2.md", "file10.md", "Zeta"]
+        );
+        assert_eq!(
+            listing.sorted(natural(true, false)),
+            ["Zeta", "file10.md", "File2.md", "docs", "a.md"]
+        );
+    }
+}
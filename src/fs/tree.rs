@@ -0,0 +1,239 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+};
+
+use super::{DirId, ListOptions, dir_id, dirs_to_open, gitignore::IgnoreRules, list_dir};
+
+// ## Tree Loading ##
+// The recursive view lists a directory's entries only once it is shown
+// open, on a background thread: unfolding a directory, or showing a tree
+// whose folders were open before, never waits on the disk. The thread lists
+// one directory per request, in the order asked.
+
+// The entries of one directory, sorted as in the flat view
+pub struct TreeListing {
+    pub dir: PathBuf,
+    // None if the directory could not be read (e.g. permission denied) or
+    // is a link back to a directory above it, which would loop
+    pub entries: Option<Vec<fs::DirEntry>>,
+}
+
+// A thread listing directories for the tree view. Dropping it cancels
+// whatever is still queued.
+pub struct TreeLoader {
+    requests: Sender<PathBuf>,
+    pub receiver: Receiver<TreeListing>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for TreeLoader {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl TreeLoader {
+    pub fn new(options: ListOptions) -> TreeLoader {
+        let (requests, queue) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+        thread::spawn(move || list_requested(&queue, &sender, options, &worker_cancel));
+        TreeLoader { requests, receiver, cancel }
+    }
+
+    // Have `dir` listed; its entries come back on `receiver`
+    pub fn request(&self, dir: PathBuf) {
+        // The thread only stops once the loader is dropped
+        let _ = self.requests.send(dir);
+    }
+}
+
+fn list_requested(
+    queue: &Receiver<PathBuf>,
+    sender: &Sender<TreeListing>,
+    options: ListOptions,
+    cancel: &AtomicBool,
+) {
+    // The ignore rules of every directory listed so far, so a subdirectory
+    // only adds its own ignore files to its parent's
+    let mut rules: HashMap<PathBuf, IgnoreRules> = HashMap::new();
+    for dir in queue {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let entries = if loops_back(&dir) {
+            None
+        } else {
+            let dir_rules = match dir.parent().and_then(|parent| rules.get(parent)) {
+                Some(parent_rules) if !options.show_ignored => parent_rules.child(&dir),
+                _ => options.rules_for(&dir),
+            };
+            let entries = list_dir(&dir, &options, &dir_rules).ok();
+            rules.insert(dir.clone(), dir_rules);
+            entries
+        };
+        // The receiver is gone if the loader was dropped
+        if sender.send(TreeListing { dir, entries }).is_err() {
+            return;
+        }
+    }
+}
+
+// Whether `dir` is a link to one of the directories above it, which the
+// tree would show again inside itself without end
+fn loops_back(dir: &Path) -> bool {
+    if !fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_symlink()) {
+        return false;
+    }
+    let Some(id) = dir_id(dir) else {
+        return false;
+    };
+    dir.ancestors().skip(1).any(|ancestor| dir_id(ancestor) == Some(id))
+}
+
+// ## Walking ##
+// Search, the finder and export go through a whole tree as the tree view
+// would show it with everything open, one directory at a time, so they can
+// work on what they found so far and stop when told.
+
+// Walk the tree below `root` depth first, in the order the tree view lists
+// it, handing each entry and its depth (0 for the entries of `root`) to
+// `visit`, which can stop the walk by returning `Break`. `cancel` is checked
+// before each directory is read. Directories below `root` that can't be
+// read are skipped.
+pub fn walk_tree(
+    root: &Path,
+    options: &ListOptions,
+    cancel: &AtomicBool,
+    mut visit: impl FnMut(fs::DirEntry, usize) -> ControlFlow<()>,
+) -> io::Result<()> {
+    // Links to directories are followed unless what they point to was
+    // opened already, which also stops links that loop. Seeded with `root`
+    // and the directories above it, so a link back up is not followed
+    // either.
+    let mut visited: HashSet<DirId> = root.ancestors().filter_map(dir_id).collect();
+    let rules = options.rules_for(root);
+    let entries = list_dir(root, options, &rules)?;
+    let open = dirs_to_open(&entries, &mut visited);
+    // The directories being gone through, innermost last
+    let mut stack = vec![(entries.into_iter(), open, rules)];
+
+    while let Some(depth) = stack.len().checked_sub(1) {
+        let (entries, open, rules) = &mut stack[depth];
+        let Some(entry) = entries.next() else {
+            stack.pop();
+            continue;
+        };
+        let path = entry.path();
+        let dir_rules = open.contains(&path).then(|| {
+            if options.show_ignored { IgnoreRules::default() } else { rules.child(&path) }
+        });
+        if visit(entry, depth).is_break() {
+            break;
+        }
+        let Some(dir_rules) = dir_rules else {
+            continue;
+        };
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        // Unreadable directories are skipped (e.g. permission denied)
+        if let Ok(entries) = list_dir(&path, options, &dir_rules) {
+            let open = dirs_to_open(&entries, &mut visited);
+            stack.push((entries.into_iter(), open, dir_rules));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::fs::symlink, time::Duration};
+
+    use super::*;
+
+    // root/
+    //   a/up -> ..      (back to root)
+    //   alias -> real   (beside its own target)
+    //   real/note.md
+    struct LinkedTree(PathBuf);
+
+    impl LinkedTree {
+        fn new(name: &str) -> LinkedTree {
+            let root = std::env::temp_dir().join(format!("greenmerald-links-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("a")).unwrap();
+            fs::create_dir_all(root.join("real")).unwrap();
+            fs::write(root.join("real/note.md"), "").unwrap();
+            symlink("..", root.join("a/up")).unwrap();
+            symlink("real", root.join("alias")).unwrap();
+            LinkedTree(root)
+        }
+    }
+
+    impl Drop for LinkedTree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn walk_opens_each_directory_once() {
+        let tree = LinkedTree::new("walk");
+        let mut walked = Vec::new();
+        walk_tree(&tree.0, &ListOptions::default(), &AtomicBool::new(false), |entry, depth| {
+            let path = entry.path();
+            walked.push((path.strip_prefix(&tree.0).unwrap().to_path_buf(), depth));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        // The link back up and the second way into `real` are listed but
+        // not opened
+        let expected: Vec<(PathBuf, usize)> = [("a", 0), ("a/up", 1), ("alias", 0), ("real", 0), ("real/note.md", 1)]
+        .iter()
+        .map(|(path, depth)| (PathBuf::from(path), *depth))
+        .collect();
+        assert_eq!(walked, expected);
+    }
+
+    #[test]
+    fn walk_stops_when_cancelled() {
+        let tree = LinkedTree::new("cancel");
+        let mut walked = 0;
+        walk_tree(&tree.0, &ListOptions::default(), &AtomicBool::new(true), |_, _| {
+            walked += 1;
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        // It stops at `a`, the first directory it would have read
+        assert_eq!(walked, 1);
+    }
+
+    #[test]
+    fn loader_leaves_links_back_up_empty() {
+        let tree = LinkedTree::new("loader");
+        assert!(loops_back(&tree.0.join("a/up")));
+        assert!(!loops_back(&tree.0.join("alias")));
+        assert!(!loops_back(&tree.0.join("real")));
+
+        let loader = TreeLoader::new(ListOptions::default());
+        loader.request(tree.0.join("a/up"));
+        loader.request(tree.0.join("alias"));
+        let up = loader.receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(up.entries.is_none());
+        let alias = loader.receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let names: Vec<_> = alias.entries.unwrap().iter().map(fs::DirEntry::file_name).collect();
+        assert_eq!(names, ["note.md"]);
+    }
+}
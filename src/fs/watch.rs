@@ -0,0 +1,80 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::ListOptions;
+
+// Watches the directories listed on screen (inotify on Linux), each on its
+// own rather than with its whole subtree, and collects the paths that
+// changed in them
+pub struct DirWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    // What is watched right now
+    watched: HashSet<PathBuf>,
+}
+
+impl DirWatcher {
+    pub fn new() -> notify::Result<DirWatcher> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away when the app exits
+            let _ = sender.send(event);
+        })?;
+        Ok(DirWatcher {
+            watcher,
+            receiver,
+            watched: HashSet::new(),
+        })
+    }
+
+    // Make the watched set exactly `wanted`. Directories that cannot be
+    // watched (removed, no permission) are skipped.
+    pub fn set_watched(&mut self, wanted: &HashSet<PathBuf>) {
+        let stale: Vec<PathBuf> = self.watched.difference(wanted).cloned().collect();
+        for path in stale {
+            let _ = self.watcher.unwatch(&path);
+            self.watched.remove(&path);
+        }
+        for path in wanted {
+            if !self.watched.contains(path) && self.watcher.watch(path, RecursiveMode::NonRecursive).is_ok() {
+                self.watched.insert(path.clone());
+            }
+        }
+    }
+
+    // Paths touched since the last call. Reads (access events) are ignored.
+    pub fn changed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for event in self.receiver.try_iter().flatten() {
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            paths.extend(event.paths);
+        }
+        paths
+    }
+}
+
+// Whether any of the `changed` paths is (or was) an entry of one of the
+// `listed` directories. Hidden and ignored ones don't count unless they are
+// shown, except for ignore files, which change what else is listed.
+pub fn affects_listing(changed: &[PathBuf], listed: &HashSet<PathBuf>, options: &ListOptions) -> bool {
+    let mut by_dir: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for path in changed {
+        if let Some(dir) = path.parent().filter(|dir| listed.contains(*dir)) {
+            by_dir.entry(dir).or_default().push(path);
+        }
+    }
+    by_dir.into_iter().any(|(dir, paths)| {
+        let rules = options.rules_for(dir);
+        paths.iter().any(|path| {
+            let ignore_file = path.file_name().is_some_and(|name| name == ".gitignore" || name == ".ignore");
+            (ignore_file && !options.show_ignored) || options.includes_path(path, path.is_dir(), &rules)
+        })
+    })
+}
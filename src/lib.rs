@@ -4,6 +4,8 @@
 // These lines tell the Rust compiler to look for the code in
 // src/app/mod.rs, src/fs/mod.rs, src/ui/mod.rs, and src/utils/mod.rs.
 pub mod app;
+pub mod cli;
+pub mod config;
 pub mod fs;
 pub mod ui;
 pub mod utils;
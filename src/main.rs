@@ -1,4 +1,6 @@
-use std::{io, time::Duration};
+use std::{io, process::{self, ExitStatus}, time::Duration};
+
+use clap::Parser;
 
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event},
@@ -16,25 +18,68 @@ use ratatui::{
 use greenmerald_markdown_renderer::{
     App,
     HandlerResult,
+    app::editor::EditRequest,
+    cli::{Cli, Command},
+    fs::set_markdown_extensions,
     ui,
+    ui::SPINNER_TICK,
 };
 
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    // Only redraw after something changed: an input, a resize or a file edit
+    let mut needs_redraw = true;
 
     loop {
         // Draw the UI
-        terminal.draw(|f| ui::<B>(f, app))?;
+        if needs_redraw {
+            terminal.draw(|f| ui::<B>(f, app))?;
+            needs_redraw = false;
+        }
 
         // Handle input events
-        if event::poll(Duration::from_millis(250))? {
-            if let Event::Key(key) = event::read()? {
-                // Call the new dedicated handler method
-                match app.handle_key_event(key) {
-                    HandlerResult::Quit => return Ok(()),
-                    HandlerResult::Continue => {}
+        // Wake up more often while the tree view is loading, to turn its
+        // spinner
+        let mut timeout = Duration::from_millis(app.config.general.poll_interval_ms);
+        if app.is_loading_tree() {
+            timeout = timeout.min(SPINNER_TICK);
+        }
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) => {
+                    // Call the new dedicated handler method
+                    match app.handle_key_event(key) {
+                        HandlerResult::Quit => return Ok(()),
+                        HandlerResult::Edit(request) => {
+                            let status = run_editor(terminal, &request)?;
+                            app.finish_edit(&request, status);
+                        }
+                        HandlerResult::Continue => {}
+                    }
+                    needs_redraw = true;
                 }
+                Event::Mouse(mouse) => needs_redraw |= app.handle_mouse_event(mouse),
+                Event::Resize(_, _) => needs_redraw = true,
+                _ => {}
             }
+        } else if app.refresh_preview_if_modified() {
+            // Nothing happened during the poll interval; check whether the
+            // previewed file was edited behind our back
+            needs_redraw = true;
+        }
+
+        // Pick up results streamed in by a search across files, the
+        // finder's index once it is built, the tree view's walk, changes
+        // on disk, link openers that failed and an export's progress
+        app.sync_watches();
+        if app.poll_file_search()
+            | app.poll_finder()
+            | app.poll_tree_walk()
+            | app.poll_watcher()
+            | app.poll_openers()
+            | app.poll_export()
+        {
+            needs_redraw = true;
         }
 
         if app.should_quit {
@@ -42,9 +87,73 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
         }
     }
 }
+// Hand the terminal to an editor: leave raw mode and the alternate screen
+// as on exit, wait for the editor, then set the TUI up again
+fn run_editor<B: Backend>(
+    terminal: &mut Terminal<B>,
+    request: &EditRequest,
+) -> io::Result<io::Result<ExitStatus>> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()?;
+
+    let status = request.command().status();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    // The editor drew over everything; repaint from scratch
+    terminal.clear()?;
+    Ok(status)
+}
+
 // ## 2. Main Function ##
 // Entry point of the application
 fn main() -> Result<(), io::Error> {
+    // Parse the command line before touching the terminal, so errors and
+    // --help print normally
+    let cli = Cli::parse();
+    if let Some(Command::Config(args)) = &cli.command {
+        // Works even when the current config file is broken
+        if let Err(e) = cli.config_command(args) {
+            eprintln!("greenmerald: {e}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("greenmerald: {e}");
+            process::exit(2);
+        }
+    };
+    set_markdown_extensions(config.markdown.extensions.clone());
+
+    // Subcommands that do their work and exit without starting the TUI
+    let finished = match &cli.command {
+        Some(Command::Render(args)) => Some(cli.render(args, &config)),
+        Some(Command::Export(args)) => Some(cli.export(args, &config)),
+        _ => None,
+    };
+    if let Some(result) = finished {
+        if let Err(e) = result {
+            eprintln!("greenmerald: {e}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    let options = match &cli.command {
+        Some(Command::View(args)) => cli.view_options(args, &config),
+        _ => cli.start_options(&config),
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("greenmerald: {e}");
+            process::exit(2);
+        }
+    };
+
     // Setup the terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -53,7 +162,7 @@ fn main() -> Result<(), io::Error> {
     let mut terminal = Terminal::new(backend)?;
 
     // Create app and run it
-    let mut app = App::new();
+    let mut app = App::with_options(options);
     let res = run_app(&mut terminal, &mut app);
 
     // Restore terminal
@@ -68,6 +177,11 @@ fn main() -> Result<(), io::Error> {
     if let Err(err) = res {
         println!("{err:?}");
     }
+    if app.config.tree.remember_folds
+        && let Err(e) = app.save_folds()
+    {
+        eprintln!("greenmerald: could not save the tree view's folds: {e}");
+    }
 
     Ok(())
 }
@@ -0,0 +1,90 @@
+use std::io::{self, Write};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Line,
+};
+
+// ## ANSI Output ##
+// Writes rendered lines to a plain terminal (or pipe) instead of a ratatui
+// buffer, for `greenmerald render`.
+
+// SGR parameter for each modifier ratatui can set
+const MODIFIER_CODES: &[(Modifier, &str)] = &[
+    (Modifier::BOLD, "1"),
+    (Modifier::DIM, "2"),
+    (Modifier::ITALIC, "3"),
+    (Modifier::UNDERLINED, "4"),
+    (Modifier::SLOW_BLINK, "5"),
+    (Modifier::RAPID_BLINK, "6"),
+    (Modifier::REVERSED, "7"),
+    (Modifier::HIDDEN, "8"),
+    (Modifier::CROSSED_OUT, "9"),
+];
+
+// Write each line followed by a newline. Without `color` only the text is
+// written.
+pub fn write_lines(out: &mut impl Write, lines: &[Line<'_>], color: bool) -> io::Result<()> {
+    for line in lines {
+        for span in &line.spans {
+            if color {
+                let style = line.style.patch(span.style);
+                match sgr(style) {
+                    Some(codes) => write!(out, "\x1b[{codes}m{}\x1b[0m", span.content)?,
+                    None => write!(out, "{}", span.content)?,
+                }
+            } else {
+                write!(out, "{}", span.content)?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// The SGR parameters for a style, or None if it is the terminal default
+fn sgr(style: Style) -> Option<String> {
+    let mut codes: Vec<String> = MODIFIER_CODES
+    .iter()
+    .filter(|(modifier, _)| style.add_modifier.contains(*modifier))
+    .map(|(_, code)| code.to_string())
+    .collect();
+    if let Some(fg) = style.fg.and_then(|color| color_code(color, false)) {
+        codes.push(fg);
+    }
+    if let Some(bg) = style.bg.and_then(|color| color_code(color, true)) {
+        codes.push(bg);
+    }
+    if codes.is_empty() {
+        None
+    } else {
+        Some(codes.join(";"))
+    }
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    // Foreground codes are 30-37 / 90-97; backgrounds are the same plus 10
+    let base = if background { 10 } else { 0 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::Gray => 37,
+        Color::DarkGray => 90,
+        Color::LightRed => 91,
+        Color::LightGreen => 92,
+        Color::LightYellow => 93,
+        Color::LightBlue => 94,
+        Color::LightMagenta => 95,
+        Color::LightCyan => 96,
+        Color::White => 97,
+        Color::Indexed(index) => return Some(format!("{};5;{index}", 38 + base)),
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{r};{g};{b}", 38 + base)),
+    };
+    Some((code + base).to_string())
+}
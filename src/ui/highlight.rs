@@ -0,0 +1,135 @@
+use std::sync::LazyLock;
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+use syntect::{
+    parsing::{ParseState, Scope, ScopeStack, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+use crate::ui::theme::{SyntaxTheme, Theme};
+
+// ## Code Block Highlighting ##
+
+// The grammars bundled with syntect, loaded on first use
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+// Token classes we colour, in the order they are tested. More specific
+// scopes must come before their prefixes (e.g. `entity.name.function`
+// before `entity.name`).
+#[derive(Clone, Copy)]
+enum TokenClass {
+    Comment,
+    Keyword,
+    String,
+    Constant,
+    Function,
+    Type,
+    Attribute,
+}
+
+static SCOPE_CLASSES: LazyLock<Vec<(Scope, TokenClass)>> = LazyLock::new(|| {
+    [
+        ("comment", TokenClass::Comment),
+        ("string", TokenClass::String),
+        ("constant.character", TokenClass::String),
+        ("constant", TokenClass::Constant),
+        ("entity.name.function", TokenClass::Function),
+        ("support.function", TokenClass::Function),
+        ("variable.function", TokenClass::Function),
+        ("entity.name.tag", TokenClass::Keyword),
+        ("entity.other.attribute-name", TokenClass::Attribute),
+        ("meta.attribute", TokenClass::Attribute),
+        ("entity.name", TokenClass::Type),
+        ("support.type", TokenClass::Type),
+        ("support.class", TokenClass::Type),
+        ("storage.type", TokenClass::Keyword),
+        ("storage", TokenClass::Keyword),
+        ("keyword", TokenClass::Keyword),
+        ("variable.language", TokenClass::Keyword),
+    ]
+    .into_iter()
+    .filter_map(|(name, class)| Scope::new(name).ok().map(|scope| (scope, class)))
+    .collect()
+});
+
+// Extracts the language token from a fence info string,
+// e.g. "rust,ignore" or "python title=example.py" -> "rust" / "python"
+pub fn fence_language(info: &str) -> &str {
+    info.split(|c: char| c == ',' || c == '{' || c.is_whitespace())
+    .next()
+    .unwrap_or("")
+}
+
+// Highlights the contents of a code block, one output line per source line.
+// Unknown or missing languages fall back to plain text in the code block style.
+pub fn highlight_code(code: &str, info: &str, theme: &Theme) -> Vec<Line<'static>> {
+    let lang = fence_language(info);
+    let syntax = match SYNTAX_SET.find_syntax_by_token(lang) {
+        Some(syntax) if !lang.is_empty() => syntax,
+        _ => return plain_lines(code, theme.code_block),
+    };
+
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    for source_line in LinesWithEndings::from(code) {
+        let ops = match state.parse_line(source_line, &SYNTAX_SET) {
+            Ok(ops) => ops,
+            // A broken grammar should never hide the code itself
+            Err(_) => return plain_lines(code, theme.code_block),
+        };
+
+        let text = source_line.trim_end_matches(['\n', '\r']);
+        let mut spans = Vec::new();
+        let mut start = 0;
+
+        for (offset, op) in ops {
+            let offset = offset.min(text.len());
+            if offset > start {
+                let style = token_style(&stack, &theme.syntax, theme.code_block);
+                spans.push(Span::styled(text[start..offset].to_string(), style));
+                start = offset;
+            }
+            let _ = stack.apply(&op);
+        }
+        if start < text.len() {
+            let style = token_style(&stack, &theme.syntax, theme.code_block);
+            spans.push(Span::styled(text[start..].to_string(), style));
+        }
+
+        lines.push(Line::from(spans));
+    }
+
+    lines
+}
+
+// Picks the style for the innermost scope that maps to a token class
+fn token_style(stack: &ScopeStack, syntax: &SyntaxTheme, base: Style) -> Style {
+    for scope in stack.as_slice().iter().rev() {
+        for (prefix, class) in SCOPE_CLASSES.iter() {
+            if prefix.is_prefix_of(*scope) {
+                let style = match class {
+                    TokenClass::Comment => syntax.comment,
+                    TokenClass::Keyword => syntax.keyword,
+                    TokenClass::String => syntax.string,
+                    TokenClass::Constant => syntax.constant,
+                    TokenClass::Function => syntax.function,
+                    TokenClass::Type => syntax.r#type,
+                    TokenClass::Attribute => syntax.attribute,
+                };
+                return base.patch(style);
+            }
+        }
+    }
+    base
+}
+
+fn plain_lines(code: &str, style: Style) -> Vec<Line<'static>> {
+    code.lines()
+    .map(|line| Line::from(Span::styled(line.to_string(), style)))
+    .collect()
+}
@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Parser, Tag, TagEnd, html};
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::fs::is_markdown_file;
+use crate::ui::highlight::highlight_code;
+use crate::ui::markdown::{parser_options, slugify, unique_anchor};
+use crate::ui::theme::Theme;
+
+// ## HTML Export ##
+// Turns a Markdown document into a standalone page styled after the theme,
+// for `greenmerald export` and the export key.
+
+// What the 16 named terminal colours are drawn as
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+// A complete HTML page for a Markdown document. The page is titled after
+// its first heading, or `fallback_title` if it has none. Links to other
+// Markdown files are pointed at their pages only with `rewrite_links`, when
+// the pages are exported too; a page exported on its own keeps them as they
+// are rather than linking to pages that don't exist.
+pub fn markdown_to_html(source: &str, fallback_title: &str, theme: &Theme, rewrite_links: bool) -> String {
+    let events: Vec<Event> = Parser::new_ext(source, parser_options()).collect();
+    let mut output = Vec::with_capacity(events.len());
+    let mut anchors: Vec<String> = Vec::new();
+    let mut title = None;
+
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::Heading { level, id, classes, attrs }) => {
+                // Give every heading the anchor the preview would, so
+                // `#section` links work in the browser too
+                let text = heading_text(&events[i + 1..]);
+                let base = id.as_ref().map_or_else(|| slugify(&text), |id| id.to_string());
+                let anchor = unique_anchor(base, |anchor| anchors.iter().any(|a| a == anchor));
+                anchors.push(anchor.clone());
+                title.get_or_insert(text);
+                output.push(Event::Start(Tag::Heading {
+                    level: *level,
+                    id: Some(CowStr::from(anchor)),
+                    classes: classes.clone(),
+                    attrs: attrs.clone(),
+                }));
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) if rewrite_links => {
+                output.push(Event::Start(Tag::Link {
+                    link_type: *link_type,
+                    dest_url: CowStr::from(rewrite_link(dest_url)),
+                    title: title.clone(),
+                    id: id.clone(),
+                }));
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let mut code = String::new();
+                i += 1;
+                while let Some(Event::Text(text)) = events.get(i) {
+                    code.push_str(text);
+                    i += 1;
+                }
+                // `i` is now on the End(CodeBlock) event, skipped below
+                output.push(Event::Html(CowStr::from(code_block_html(&code, &info, theme))));
+            }
+            event => output.push(event.clone()),
+        }
+        i += 1;
+    }
+
+    let mut body = String::new();
+    html::push_html(&mut body, output.into_iter());
+    let title = title.unwrap_or_else(|| fallback_title.to_string());
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&title),
+        theme_css(theme),
+        body
+    )
+}
+
+// The plain text of a heading, from the events after its Start
+fn heading_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::End(TagEnd::Heading(_)) => break,
+            Event::Text(t) | Event::Code(t) => text.push_str(t),
+            _ => {}
+        }
+    }
+    text
+}
+
+// Point links to Markdown files at the page exported next to them:
+// "guide.md#install" -> "guide.html#install"
+fn rewrite_link(target: &str) -> String {
+    if target.contains("://") || target.starts_with("mailto:") || target.starts_with('#') {
+        return target.to_string();
+    }
+    let (path, anchor) = match target.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor)),
+        None => (target, None),
+    };
+    if !is_markdown_file(Path::new(path)) {
+        return target.to_string();
+    }
+    let page = Path::new(path).with_extension("html");
+    let page = page.to_string_lossy();
+    match anchor {
+        Some(anchor) => format!("{page}#{anchor}"),
+        None => page.into_owned(),
+    }
+}
+
+// A code block, highlighted with the theme's syntax colours
+fn code_block_html(code: &str, info: &str, theme: &Theme) -> String {
+    let mut html = String::from("<pre><code>");
+    for line in highlight_code(code, info, theme) {
+        // Neighbouring tokens often share a colour; give each run one <span>
+        let mut runs: Vec<(String, String)> = Vec::new();
+        for span in &line.spans {
+            let css = style_css(line.style.patch(span.style));
+            match runs.last_mut() {
+                Some((last_css, text)) if *last_css == css => text.push_str(&span.content),
+                _ => runs.push((css, span.content.to_string())),
+            }
+        }
+        for (css, text) in runs {
+            if css.is_empty() {
+                html.push_str(&escape_html(&text));
+            } else {
+                html.push_str(&format!("<span style=\"{css}\">{}</span>", escape_html(&text)));
+            }
+        }
+        html.push('\n');
+    }
+    html.push_str("</code></pre>\n");
+    html
+}
+
+// The page's stylesheet, one rule per themed Markdown element
+fn theme_css(theme: &Theme) -> String {
+    let background = color_css(theme.page_background).unwrap_or_else(|| "white".to_string());
+    let foreground = color_css(theme.page_foreground).unwrap_or_else(|| "black".to_string());
+    let mut rules = vec![
+        format!(
+            "body {{ background-color: {background}; color: {foreground}; \
+             font-family: system-ui, sans-serif; line-height: 1.5; \
+             max-width: 50em; margin: 2em auto; padding: 0 1em; }}"
+        ),
+        // A shade of whatever the page background is
+        "pre { padding: 0.75em 1em; overflow-x: auto; border-radius: 4px; \
+         background-color: rgba(127, 127, 127, 0.12); }".to_string(),
+        "pre, code { font-family: ui-monospace, monospace; }".to_string(),
+        "pre code { background: none; }".to_string(),
+        "blockquote { margin-left: 0; padding-left: 1em; border-left: 3px solid; }".to_string(),
+        "table { border-collapse: collapse; }".to_string(),
+        "th, td { padding: 0.25em 0.75em; border: 1px solid; }".to_string(),
+        "hr { border: none; border-top: 1px solid; }".to_string(),
+    ];
+    for (level, style) in theme.headings.iter().enumerate() {
+        rules.push(format!("h{} {{ {} }}", level + 1, style_css(*style)));
+    }
+    for (selector, style) in [
+        ("em", theme.emphasis),
+        ("strong", theme.strong),
+        ("del", theme.strikethrough),
+        ("blockquote", theme.blockquote),
+        ("code", theme.code),
+        // The block's own style wins over inline `code` inside it
+        ("pre, pre code", theme.code_block),
+        ("a", theme.link),
+        ("hr", theme.rule),
+        ("th", theme.table_header),
+        ("li::marker", theme.list_marker),
+    ] {
+        rules.push(format!("{selector} {{ {} }}", style_css(style)));
+    }
+    // Only the borders of cells take the table border colour
+    if let Some(border) = theme.table_border.fg.and_then(color_css) {
+        rules.push(format!("th, td {{ border-color: {border}; }}"));
+    }
+    rules.join("\n") + "\n"
+}
+
+// CSS declarations for a terminal style
+fn style_css(style: Style) -> String {
+    let mut css = Vec::new();
+    if let Some(fg) = style.fg.and_then(color_css) {
+        css.push(format!("color: {fg};"));
+    }
+    if let Some(bg) = style.bg.and_then(color_css) {
+        css.push(format!("background-color: {bg};"));
+    }
+    let modifiers = style.add_modifier;
+    if modifiers.contains(Modifier::BOLD) {
+        css.push("font-weight: bold;".to_string());
+    }
+    if modifiers.contains(Modifier::DIM) {
+        css.push("opacity: 0.7;".to_string());
+    }
+    if modifiers.contains(Modifier::ITALIC) {
+        css.push("font-style: italic;".to_string());
+    }
+    match (modifiers.contains(Modifier::UNDERLINED), modifiers.contains(Modifier::CROSSED_OUT)) {
+        (true, true) => css.push("text-decoration: underline line-through;".to_string()),
+        (true, false) => css.push("text-decoration: underline;".to_string()),
+        (false, true) => css.push("text-decoration: line-through;".to_string()),
+        (false, false) => {}
+    }
+    css.join(" ")
+}
+
+fn color_css(color: Color) -> Option<String> {
+    let index = match color {
+        Color::Reset => return None,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+        Color::Rgb(r, g, b) => return Some(format!("#{r:02x}{g:02x}{b:02x}")),
+        Color::Indexed(index) => return Some(indexed_color(index)),
+    };
+    Some(PALETTE[index].to_string())
+}
+
+// The xterm 256-colour palette: the 16 named colours, a 6x6x6 colour cube
+// and a grey ramp
+fn indexed_color(index: u8) -> String {
+    match index {
+        0..=15 => PALETTE[index as usize].to_string(),
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            format!("#{:02x}{:02x}{:02x}", level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let grey = 8 + (index - 232) * 10;
+            format!("#{grey:02x}{grey:02x}{grey:02x}")
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,919 @@
+use pulldown_cmark::{Alignment, CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use ratatui::{
+    style::Style,
+    text::{Line, Span, Text},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::ui::highlight::highlight_code;
+use crate::ui::table::Table;
+use crate::ui::theme::Theme;
+
+// Width used before the document has been laid out for a real pane
+const DEFAULT_WIDTH: u16 = 80;
+
+// ## Markdown Document Cache ##
+
+// A link found in a rendered document
+pub struct Link {
+    // The raw destination, e.g. "docs/guide.md", "#usage" or a URL
+    pub target: String,
+    // Where the link text was drawn. Empty for links inside tables, which
+    // are laid out as a whole and only indexed by their first line.
+    pub regions: Vec<LinkRegion>,
+    // First rendered line of the link
+    pub line: usize,
+}
+
+// A run of columns on one rendered line covered by a link's text
+pub struct LinkRegion {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// A heading found in a rendered document
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+    // GitHub-style anchor ("Getting Started" -> "getting-started"), or the
+    // explicit `{#id}` attribute when one is given
+    pub anchor: String,
+    // Rendered line the heading starts on
+    pub line: usize,
+}
+
+// The output of laying out a document for one width
+struct Rendered {
+    lines: Vec<Line<'static>>,
+    links: Vec<Link>,
+    headings: Vec<Heading>,
+    source_lines: Vec<(usize, usize)>,
+}
+
+// A Markdown file that is rendered once and kept laid out for the width of
+// the preview pane. Rendering on every frame is expensive for large files, so
+// the draw loop only asks the document to re-wrap itself, which is a no-op
+// unless the pane width actually changed.
+pub struct MarkdownDocument {
+    source: String,
+    theme: Theme,
+    // Rendered lines, wrapped to `width`, ready to hand to a Paragraph
+    text: Text<'static>,
+    links: Vec<Link>,
+    headings: Vec<Heading>,
+    // (source line, rendered line) for the start of every block, in order
+    source_lines: Vec<(usize, usize)>,
+    // Width the cached text was laid out for
+    width: u16,
+}
+
+impl MarkdownDocument {
+    pub fn new(source: String, theme: &Theme) -> MarkdownDocument {
+        let rendered = render(&source, theme, DEFAULT_WIDTH as usize);
+        MarkdownDocument {
+            source,
+            theme: theme.clone(),
+            text: Text::from(rendered.lines),
+            links: rendered.links,
+            headings: rendered.headings,
+            source_lines: rendered.source_lines,
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    // The rendered document, wrapped to the last width passed to `rewrap`
+    pub fn text(&self) -> &Text<'static> {
+        &self.text
+    }
+
+    // Number of rendered (wrapped) lines
+    pub fn height(&self) -> usize {
+        self.text.lines.len()
+    }
+
+    // Links in document order
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    // Headings in document order
+    pub fn headings(&self) -> &[Heading] {
+        &self.headings
+    }
+
+    // Rendered line of the heading with the given anchor (without the '#')
+    pub fn anchor_line(&self, anchor: &str) -> Option<usize> {
+        self.headings
+        .iter()
+        .find(|heading| heading.anchor.eq_ignore_ascii_case(anchor))
+        .map(|heading| heading.line)
+    }
+
+    // Rendered line of the block containing a (0-based) line of the source
+    pub fn line_for_source(&self, source_line: usize) -> usize {
+        let index = self
+        .source_lines
+        .partition_point(|&(source, _)| source <= source_line);
+        index.checked_sub(1).map_or(0, |i| self.source_lines[i].1)
+    }
+
+    // Source line of the block shown at a rendered line; the inverse of
+    // `line_for_source`
+    pub fn source_line_at(&self, line: usize) -> usize {
+        let index = self
+        .source_lines
+        .partition_point(|&(_, rendered)| rendered <= line);
+        index.checked_sub(1).map_or(0, |i| self.source_lines[i].0)
+    }
+
+    // Lay the document out again for a new pane width.
+    // Returns true if the cached text changed.
+    pub fn rewrap(&mut self, width: u16) -> bool {
+        if width == self.width || width == 0 {
+            return false;
+        }
+        self.width = width;
+        self.layout();
+        true
+    }
+
+    // Draw the document in another theme's colours. The layout stays the
+    // same, so line numbers (links, headings, search matches) still hold.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.theme = theme.clone();
+        self.layout();
+    }
+
+    fn layout(&mut self) {
+        let rendered = render(&self.source, &self.theme, self.width as usize);
+        self.text = Text::from(rendered.lines);
+        self.links = rendered.links;
+        self.headings = rendered.headings;
+        self.source_lines = rendered.source_lines;
+    }
+}
+
+// ## Markdown Rendering ##
+
+// The Markdown extensions we parse, shared with the HTML export
+pub fn parser_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    options
+}
+
+// Lays Markdown out into styled lines no wider than `width` columns
+fn render(source: &str, theme: &Theme, width: usize) -> Rendered {
+    let options = parser_options();
+    let mut writer = TextWriter::new(theme, width);
+    let mut source_lines = Vec::new();
+    // Newlines counted up to `counted`, so finding the line of each block is
+    // a single pass over the source
+    let (mut counted, mut line) = (0, 0);
+    for (event, range) in Parser::new_ext(source, options).into_offset_iter() {
+        let is_start = matches!(event, Event::Start(_));
+        writer.handle_event(event);
+        if is_start && range.start >= counted {
+            line += source.as_bytes()[counted..range.start].iter().filter(|&&b| b == b'\n').count();
+            counted = range.start;
+            // The block's first line is the next one the writer emits
+            source_lines.push((line, writer.lines.len()));
+        }
+    }
+    let mut rendered = writer.finish();
+    rendered.source_lines = source_lines;
+    rendered
+}
+
+// Turns heading text into a GitHub-style anchor: lowercase, spaces become
+// dashes and punctuation other than '-' and '_' is dropped
+pub fn slugify(text: &str) -> String {
+    text.trim()
+    .chars()
+    .filter_map(|c| {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            Some(c.to_lowercase().collect::<String>())
+        } else if c.is_whitespace() {
+            Some("-".to_string())
+        } else {
+            None
+        }
+    })
+    .collect()
+}
+
+// Duplicate anchors get a numeric suffix, as on GitHub
+pub fn unique_anchor(base: String, taken: impl Fn(&str) -> bool) -> String {
+    let mut anchor = base.clone();
+    let mut n = 1;
+    while taken(&anchor) {
+        anchor = format!("{base}-{n}");
+        n += 1;
+    }
+    anchor
+}
+
+// A line prefix (blockquote bar, list marker). `first` is used on the first
+// line it applies to and `rest` on every line after that.
+struct Prefix {
+    first: Span<'static>,
+    rest: Span<'static>,
+    used: bool,
+}
+
+// A fenced or indented code block being collected
+struct CodeBlock {
+    info: String,
+    content: String,
+}
+
+// Converts a stream of pulldown-cmark events into wrapped, styled lines
+struct TextWriter<'t> {
+    theme: &'t Theme,
+    width: usize,
+    lines: Vec<Line<'static>>,
+    // The logical line being built; wrapped when it is flushed
+    line: Option<Line<'static>>,
+    // Stack of inline styles (emphasis, strong, links...)
+    inline_styles: Vec<Style>,
+    // Stack of styles applied to whole lines (blockquotes)
+    line_styles: Vec<Style>,
+    prefixes: Vec<Prefix>,
+    // Current list numbering as a stack (None for bullet lists)
+    list_indices: Vec<Option<u64>>,
+    // Target of the link being written, appended once the link closes
+    link: Option<String>,
+    links: Vec<Link>,
+    // Link segments on the current logical line: (link index, start, end)
+    // in columns of the unwrapped line
+    line_links: Vec<(usize, usize, usize)>,
+    // Link that is still open, and the column its text starts at
+    open_link: Option<(usize, usize)>,
+    headings: Vec<Heading>,
+    // Heading being written: (level, explicit id, text so far)
+    heading: Option<(usize, Option<String>, String)>,
+    code_block: Option<CodeBlock>,
+    // Table being collected; laid out once it is complete
+    table: Option<Table>,
+    needs_newline: bool,
+}
+
+impl<'t> TextWriter<'t> {
+    fn new(theme: &'t Theme, width: usize) -> Self {
+        TextWriter {
+            theme,
+            width,
+            lines: Vec::new(),
+            line: None,
+            inline_styles: Vec::new(),
+            line_styles: Vec::new(),
+            prefixes: Vec::new(),
+            list_indices: Vec::new(),
+            link: None,
+            links: Vec::new(),
+            line_links: Vec::new(),
+            open_link: None,
+            headings: Vec::new(),
+            heading: None,
+            code_block: None,
+            table: None,
+            needs_newline: false,
+        }
+    }
+
+    fn finish(mut self) -> Rendered {
+        self.flush_line();
+        Rendered {
+            lines: self.lines,
+            links: self.links,
+            headings: self.headings,
+            source_lines: Vec::new(),
+        }
+    }
+
+    fn handle_event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start_tag(tag),
+            Event::End(tag) => self.end_tag(tag),
+            Event::Text(text) => self.text(text),
+            Event::Code(code) => {
+                if let Some((_, _, text)) = &mut self.heading {
+                    text.push_str(&code);
+                }
+                self.push_span(Span::styled(code.to_string(), self.theme.code))
+            }
+            Event::SoftBreak => self.text(CowStr::Borrowed(" ")),
+            Event::HardBreak => {
+                self.flush_line();
+                self.line = Some(Line::default());
+            }
+            Event::Rule => self.rule(),
+            Event::TaskListMarker(checked) => {
+                self.push_span(Span::raw(if checked { "[x] " } else { "[ ] " }))
+            }
+            // Raw HTML, footnotes and math are not rendered
+            _ => {}
+        }
+    }
+
+    fn start_tag(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Paragraph => self.start_block(),
+            Tag::Heading { level, id, .. } => self.start_heading(level, id.map(|id| id.to_string())),
+            Tag::BlockQuote(_) => self.start_blockquote(),
+            Tag::CodeBlock(kind) => self.start_codeblock(kind),
+            Tag::List(start_index) => self.start_list(start_index),
+            Tag::Item => self.start_item(),
+            Tag::Table(alignments) => self.start_table(alignments),
+            Tag::Emphasis => self.push_inline_style(self.theme.emphasis),
+            Tag::Strong => self.push_inline_style(self.theme.strong),
+            Tag::Strikethrough => self.push_inline_style(self.theme.strikethrough),
+            Tag::Link { dest_url, .. } => self.start_link(dest_url.to_string()),
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.end_block(),
+            TagEnd::Heading(_) => self.end_heading(),
+            TagEnd::BlockQuote(_) => {
+                self.end_block();
+                self.prefixes.pop();
+                self.line_styles.pop();
+            }
+            TagEnd::CodeBlock => self.end_codeblock(),
+            TagEnd::List(_) => {
+                self.end_block();
+                self.list_indices.pop();
+            }
+            TagEnd::Item => {
+                self.flush_line();
+                self.prefixes.pop();
+            }
+            TagEnd::Table => self.end_table(),
+            TagEnd::TableHead => self.table.iter_mut().for_each(Table::end_head),
+            TagEnd::TableRow => self.table.iter_mut().for_each(Table::end_row),
+            TagEnd::TableCell => self.table.iter_mut().for_each(Table::end_cell),
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.inline_styles.pop();
+            }
+            TagEnd::Link => self.end_link(),
+            _ => {}
+        }
+    }
+
+    // -- Blocks --
+
+    fn start_block(&mut self) {
+        if self.needs_newline {
+            self.blank_line();
+        }
+        self.new_line();
+        self.needs_newline = false;
+    }
+
+    fn end_block(&mut self) {
+        self.flush_line();
+        self.needs_newline = true;
+    }
+
+    fn start_heading(&mut self, level: HeadingLevel, id: Option<String>) {
+        self.start_block();
+        let style = self.theme.headings[level as usize - 1];
+        if let Some(line) = &mut self.line {
+            line.style = style;
+        }
+        self.push_span(Span::raw(format!("{} ", "#".repeat(level as usize))));
+        self.heading = Some((level as usize, id, String::new()));
+    }
+
+    fn end_heading(&mut self) {
+        if let Some((level, id, text)) = self.heading.take() {
+            let base = id.unwrap_or_else(|| slugify(&text));
+            let anchor = unique_anchor(base, |anchor| {
+                self.headings.iter().any(|heading| heading.anchor == anchor)
+            });
+            self.headings.push(Heading {
+                level,
+                text,
+                anchor,
+                line: self.lines.len(),
+            });
+        }
+        self.end_block();
+    }
+
+    fn start_blockquote(&mut self) {
+        if self.needs_newline {
+            self.blank_line();
+            self.needs_newline = false;
+        }
+        self.flush_line();
+        let bar = Span::styled("> ", self.theme.blockquote);
+        self.prefixes.push(Prefix {
+            first: bar.clone(),
+            rest: bar,
+            used: false,
+        });
+        self.line_styles.push(self.theme.blockquote);
+    }
+
+    fn start_codeblock(&mut self, kind: CodeBlockKind<'_>) {
+        if self.needs_newline {
+            self.blank_line();
+        }
+        self.flush_line();
+        let info = match kind {
+            CodeBlockKind::Fenced(info) => info.to_string(),
+            CodeBlockKind::Indented => String::new(),
+        };
+        self.emit_line(Line::styled(format!("```{info}"), self.theme.code_fence));
+        self.code_block = Some(CodeBlock {
+            info,
+            content: String::new(),
+        });
+    }
+
+    fn end_codeblock(&mut self) {
+        if let Some(block) = self.code_block.take() {
+            for line in highlight_code(&block.content, &block.info, self.theme) {
+                self.emit_line(line);
+            }
+        }
+        self.emit_line(Line::styled("```", self.theme.code_fence));
+        self.needs_newline = true;
+    }
+
+    fn start_list(&mut self, index: Option<u64>) {
+        if self.list_indices.is_empty() && self.needs_newline {
+            self.blank_line();
+        }
+        self.flush_line();
+        self.list_indices.push(index);
+    }
+
+    fn start_item(&mut self) {
+        self.flush_line();
+        let marker = match self.list_indices.last_mut() {
+            Some(Some(index)) => {
+                *index += 1;
+                format!("{}. ", *index - 1)
+            }
+            _ => "- ".to_string(),
+        };
+        let indent = Span::raw(" ".repeat(marker.width()));
+        self.prefixes.push(Prefix {
+            first: Span::styled(marker, self.theme.list_marker),
+            rest: indent,
+            used: false,
+        });
+        self.new_line();
+        self.needs_newline = false;
+    }
+
+    fn start_table(&mut self, alignments: Vec<Alignment>) {
+        if self.needs_newline {
+            self.blank_line();
+        }
+        self.flush_line();
+        self.table = Some(Table::new(alignments));
+    }
+
+    fn end_table(&mut self) {
+        if let Some(table) = self.table.take() {
+            let width = self.width.saturating_sub(self.prefix_width()).max(1);
+            for line in table.render(width, self.theme) {
+                self.emit_line(line);
+            }
+        }
+        self.needs_newline = true;
+    }
+
+    fn rule(&mut self) {
+        if self.needs_newline {
+            self.blank_line();
+        }
+        self.flush_line();
+        let width = self.width.saturating_sub(self.prefix_width()).max(1);
+        self.emit_line(Line::styled("─".repeat(width), self.theme.rule));
+        self.needs_newline = true;
+    }
+
+    // -- Inline content --
+
+    fn text(&mut self, text: CowStr<'_>) {
+        if let Some(block) = &mut self.code_block {
+            block.content.push_str(&text);
+            return;
+        }
+        if let Some((_, _, heading)) = &mut self.heading {
+            heading.push_str(&text);
+        }
+        let style = self.inline_styles.last().copied().unwrap_or_default();
+        self.push_span(Span::styled(text.to_string(), style));
+    }
+
+    fn start_link(&mut self, target: String) {
+        self.push_inline_style(self.theme.link);
+        let line = self.lines.len();
+        self.links.push(Link {
+            target: target.clone(),
+            regions: Vec::new(),
+            line,
+        });
+        self.open_link = Some((self.links.len() - 1, self.line_width()));
+        self.link = Some(target);
+    }
+
+    fn end_link(&mut self) {
+        self.inline_styles.pop();
+        if let Some((index, start)) = self.open_link.take()
+            && self.table.is_none()
+        {
+            self.line_links.push((index, start, self.line_width()));
+        }
+        if let Some(link) = self.link.take() {
+            self.push_span(Span::raw(" ("));
+            self.push_span(Span::styled(link, self.theme.link));
+            self.push_span(Span::raw(")"));
+        }
+    }
+
+    fn push_inline_style(&mut self, style: Style) {
+        let current = self.inline_styles.last().copied().unwrap_or_default();
+        self.inline_styles.push(current.patch(style));
+    }
+
+    fn push_span(&mut self, span: Span<'static>) {
+        if let Some(table) = &mut self.table {
+            table.push_span(span);
+            return;
+        }
+        self.line.get_or_insert_with(Line::default).push_span(span);
+    }
+
+    // -- Line management --
+
+    // Start a new logical line, unless the current one is still empty
+    fn new_line(&mut self) {
+        if self.line.as_ref().is_some_and(|line| line.spans.is_empty()) {
+            return;
+        }
+        self.flush_line();
+        self.line = Some(Line::default());
+    }
+
+    // Emit an empty separator line carrying only the continuation prefixes
+    fn blank_line(&mut self) {
+        self.flush_line();
+        let spans = self
+        .prefixes
+        .iter()
+        .map(|prefix| if prefix.used { prefix.rest.clone() } else { Span::raw(" ".repeat(prefix.rest.width())) })
+        .collect::<Vec<_>>();
+        let style = self.line_styles.last().copied().unwrap_or_default();
+        self.lines.push(Line::from(spans).style(style));
+    }
+
+    // Width of the logical line built so far
+    fn line_width(&self) -> usize {
+        self.line.as_ref().map_or(0, Line::width)
+    }
+
+    fn prefix_width(&self) -> usize {
+        self.prefixes.iter().map(|prefix| prefix.rest.width()).sum()
+    }
+
+    // Wrap the current logical line to the available width and emit it
+    fn flush_line(&mut self) {
+        let Some(line) = self.line.take() else {
+            return;
+        };
+        // A link still open at a hard break continues on the next line
+        if let Some((index, start)) = &mut self.open_link
+            && self.table.is_none()
+        {
+            self.line_links.push((*index, *start, line.width()));
+            *start = 0;
+        }
+        let links = std::mem::take(&mut self.line_links);
+        self.emit_line_with_links(line, &links);
+    }
+
+    // Wrap a finished line and push it with the active prefixes and line style
+    fn emit_line(&mut self, line: Line<'static>) {
+        self.emit_line_with_links(line, &[]);
+    }
+
+    fn emit_line_with_links(&mut self, line: Line<'static>, links: &[(usize, usize, usize)]) {
+        let prefix_width = self.prefix_width();
+        let available = self.width.saturating_sub(prefix_width).max(1);
+        let mut wrapped = Vec::new();
+        let starts = wrap_line(&line, available, &mut wrapped);
+
+        // Map link columns on the unwrapped line onto the wrapped pieces
+        for (piece_index, piece) in wrapped.iter().enumerate() {
+            let piece_start = starts[piece_index];
+            let piece_end = piece_start + piece.width();
+            for &(link, start, end) in links {
+                let (start, end) = (start.max(piece_start), end.min(piece_end));
+                if start < end {
+                    self.links[link].regions.push(LinkRegion {
+                        line: self.lines.len() + piece_index,
+                        start: prefix_width + start - piece_start,
+                        end: prefix_width + end - piece_start,
+                    });
+                }
+            }
+        }
+        for &(link, _, _) in links {
+            if let Some(first) = self.links[link].regions.first() {
+                self.links[link].line = first.line;
+            }
+        }
+
+        let line_style = self.line_styles.last().copied().unwrap_or_default();
+        for mut piece in wrapped {
+            let mut spans = Vec::with_capacity(self.prefixes.len() + piece.spans.len());
+            for prefix in &mut self.prefixes {
+                if prefix.used {
+                    spans.push(prefix.rest.clone());
+                } else {
+                    spans.push(prefix.first.clone());
+                    prefix.used = true;
+                }
+            }
+            spans.append(&mut piece.spans);
+            self.lines.push(Line {
+                spans,
+                style: line_style.patch(piece.style),
+                alignment: piece.alignment,
+            });
+        }
+    }
+}
+
+// Word-wraps a styled line to `width` columns, keeping span styles intact.
+// Words longer than the width are hard-split. Returns, for each line pushed
+// to `out`, the column of the original line it starts at.
+pub(crate) fn wrap_line(line: &Line<'static>, width: usize, out: &mut Vec<Line<'static>>) -> Vec<usize> {
+    if width == 0 || line.width() <= width {
+        out.push(line.clone());
+        return vec![0];
+    }
+
+    let blank = || Line {
+        spans: Vec::new(),
+        style: line.style,
+        alignment: line.alignment,
+    };
+    let mut starts = Vec::new();
+    let mut current = blank();
+    let mut current_width = 0;
+    // Column of the original line we have consumed up to
+    let mut column = 0;
+    // Column the current piece starts at
+    let mut current_start = None;
+
+    for span in &line.spans {
+        for word in split_keep_whitespace(&span.content) {
+            let is_space = word.chars().all(char::is_whitespace);
+            let word_width = word.width();
+
+            if current_width + word_width > width {
+                if current_width > 0 {
+                    trim_end(&mut current);
+                    out.push(std::mem::replace(&mut current, blank()));
+                    starts.push(current_start.take().unwrap_or(column));
+                    current_width = 0;
+                }
+                if is_space {
+                    // Whitespace at a wrap point is swallowed
+                    column += word_width;
+                    continue;
+                }
+            }
+
+            if word_width <= width {
+                current_start.get_or_insert(column);
+                push_str(&mut current, word, span.style);
+                current_width += word_width;
+                column += word_width;
+                continue;
+            }
+
+            // Hard-split words that can never fit on one line
+            for ch in word.chars() {
+                let ch_width = ch.width().unwrap_or(0);
+                if current_width + ch_width > width && current_width > 0 {
+                    out.push(std::mem::replace(&mut current, blank()));
+                    starts.push(current_start.take().unwrap_or(column));
+                    current_width = 0;
+                }
+                current_start.get_or_insert(column);
+                let mut buf = [0; 4];
+                push_str(&mut current, ch.encode_utf8(&mut buf), span.style);
+                current_width += ch_width;
+                column += ch_width;
+            }
+        }
+    }
+
+    if !current.spans.is_empty() || starts.is_empty() {
+        out.push(current);
+        starts.push(current_start.unwrap_or(column));
+    }
+    starts
+}
+
+// Wraps plain text (non-Markdown previews) the same way rendered documents
+// are wrapped, so the preview can scroll and search it line by line
+pub fn wrap_text(text: &str, width: usize) -> Vec<Line<'static>> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        wrap_line(&Line::raw(line.to_string()), width, &mut out);
+    }
+    out
+}
+
+// Returns a copy of `line` with `style` patched onto columns start..end
+pub fn patch_columns(line: &Line<'static>, start: usize, end: usize, style: Style) -> Line<'static> {
+    let mut patched = Line {
+        spans: Vec::with_capacity(line.spans.len() + 2),
+        style: line.style,
+        alignment: line.alignment,
+    };
+    let mut column = 0;
+    for span in &line.spans {
+        let span_end = column + span.content.width();
+        if span_end <= start || column >= end {
+            patched.spans.push(span.clone());
+            column = span_end;
+            continue;
+        }
+        // Split the span into the parts before, inside and after the range
+        let (mut before, mut inside, mut after) = (String::new(), String::new(), String::new());
+        for ch in span.content.chars() {
+            let target = if column < start {
+                &mut before
+            } else if column < end {
+                &mut inside
+            } else {
+                &mut after
+            };
+            target.push(ch);
+            column += ch.width().unwrap_or(0);
+        }
+        for (text, part_style) in [
+            (before, span.style),
+            (inside, span.style.patch(style)),
+            (after, span.style),
+        ] {
+            if !text.is_empty() {
+                patched.spans.push(Span::styled(text, part_style));
+            }
+        }
+    }
+    patched
+}
+
+// Drops trailing whitespace left at a wrap point
+fn trim_end(line: &mut Line<'static>) {
+    while let Some(last) = line.spans.last_mut() {
+        let trimmed = last.content.trim_end().len();
+        if trimmed > 0 {
+            last.content.to_mut().truncate(trimmed);
+            return;
+        }
+        line.spans.pop();
+    }
+}
+
+// Appends text to a line, merging it into the last span if the style matches
+fn push_str(line: &mut Line<'static>, s: &str, style: Style) {
+    match line.spans.last_mut() {
+        Some(last) if last.style == style => last.content.to_mut().push_str(s),
+        _ => line.spans.push(Span::styled(s.to_string(), style)),
+    }
+}
+
+// Splits a string into alternating runs of whitespace and non-whitespace
+fn split_keep_whitespace(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+
+    for (i, ch) in s.char_indices() {
+        let is_space = ch.is_whitespace();
+        match in_space {
+            Some(prev) if prev != is_space => {
+                parts.push(&s[start..i]);
+                start = i;
+            }
+            _ => {}
+        }
+        in_space = Some(is_space);
+    }
+    if start < s.len() {
+        parts.push(&s[start..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::{Color, Stylize};
+
+    use super::*;
+
+    // Each wrapped line as plain text
+    fn wrapped(line: Line<'static>, width: usize) -> (Vec<String>, Vec<usize>) {
+        let mut out = Vec::new();
+        let starts = wrap_line(&line, width, &mut out);
+        (out.iter().map(Line::to_string).collect(), starts)
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_swallows_them() {
+        let (lines, starts) = wrapped(Line::raw("hello world foo"), 11);
+        assert_eq!(lines, ["hello world", "foo"]);
+        assert_eq!(starts, [0, 12]);
+        // A line that fits is left alone
+        assert_eq!(wrapped(Line::raw("hello"), 5).0, ["hello"]);
+    }
+
+    #[test]
+    fn hard_splits_words_longer_than_the_width() {
+        let (lines, starts) = wrapped(Line::raw("ab abcdefghij"), 4);
+        assert_eq!(lines, ["ab", "abcd", "efgh", "ij"]);
+        assert_eq!(starts, [0, 3, 7, 11]);
+    }
+
+    #[test]
+    fn wraps_wide_characters_by_display_width() {
+        // Two columns each, so three fit in 6 and only two in 5
+        let (lines, starts) = wrapped(Line::raw("日本語テキスト"), 6);
+        assert_eq!(lines, ["日本語", "テキス", "ト"]);
+        assert_eq!(starts, [0, 6, 12]);
+        let (lines, _) = wrapped(Line::raw("日本語"), 5);
+        assert_eq!(lines, ["日本", "語"]);
+        assert!(lines.iter().all(|line| line.width() <= 5));
+    }
+
+    #[test]
+    fn wrapping_keeps_span_styles() {
+        let line = Line::from(vec![Span::raw("plain "), "red words".fg(Color::Red)]);
+        let mut out = Vec::new();
+        wrap_line(&line, 10, &mut out);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[1].to_string(), "words");
+        assert!(out[1].spans.iter().all(|span| span.style.fg == Some(Color::Red)));
+        assert_eq!(out[0].spans[0].style.fg, None);
+    }
+
+    #[test]
+    fn slugs_follow_github() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Déjà vu  "), "déjà-vu");
+        assert_eq!(slugify("snake_case and-dash"), "snake_case-and-dash");
+        assert_eq!(slugify("`code` (v2.0)"), "code-v20");
+    }
+
+    #[test]
+    fn duplicate_anchors_get_a_number() {
+        let taken = ["foo", "foo-1"];
+        assert_eq!(unique_anchor("bar".to_string(), |anchor| taken.contains(&anchor)), "bar");
+        assert_eq!(unique_anchor("foo".to_string(), |anchor| taken.contains(&anchor)), "foo-2");
+    }
+
+    #[test]
+    fn preview_and_export_give_headings_the_same_anchors() {
+        let source = "# Foo\n\ntext\n\n# Foo\n\n## Foo\n";
+        let doc = MarkdownDocument::new(source.to_string(), &Theme::default());
+        let anchors: Vec<&str> = doc.headings().iter().map(|heading| heading.anchor.as_str()).collect();
+        assert_eq!(anchors, ["foo", "foo-1", "foo-2"]);
+        assert_eq!(doc.anchor_line("foo-1"), Some(doc.headings()[1].line));
+
+        let page = crate::ui::html::markdown_to_html(source, "Foo", &Theme::default(), false);
+        for anchor in anchors {
+            assert!(page.contains(&format!("id=\"{anchor}\"")), "{anchor} missing from {page}");
+        }
+    }
+
+    #[test]
+    fn source_lines_map_to_the_rendered_blocks() {
+        let source = "# Title\n\nfirst paragraph\n\n## Next\n\nlast\n";
+        let doc = MarkdownDocument::new(source.to_string(), &Theme::default());
+        let next = doc.headings()[1].line;
+        assert_eq!(doc.line_for_source(0), 0);
+        assert_eq!(doc.line_for_source(4), next);
+        // A line inside a block goes to the start of the block
+        assert_eq!(doc.line_for_source(5), next);
+        assert_eq!(doc.source_line_at(next), 4);
+        assert!(doc.line_for_source(6) > next);
+    }
+}
@@ -1,22 +1,46 @@
 use ratatui::{
     Frame,
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
-    text::Text,
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
 };
-use tui_markdown;
-use crate::app::state::{PreviewState, App};
+use std::time::{Duration, SystemTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use crate::app::keymap::pending_text;
+use crate::app::state::{InputMode, PagerSource, PreviewState, App};
+use crate::app::file_search::FileSearch;
+use crate::app::search::PreviewSearch;
+use crate::ui::markdown::{MarkdownDocument, patch_columns, wrap_text};
+use crate::ui::theme::Theme;
 use crate::ui::widget::format_entry_flat;
+use crate::ui::widget::format_heading;
+use crate::ui::widget::format_hit;
+use crate::ui::widget::format_candidate;
 use crate::ui::widget::format_entry_tree;
 
+pub mod ansi;
+pub mod highlight;
+pub mod html;
+pub mod markdown;
+pub mod table;
+pub mod theme;
 pub mod widget;
 
+// The spinner shown while the tree view fills in moves on a frame per tick
+pub const SPINNER_TICK: Duration = Duration::from_millis(100);
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 // ## 4. UI Drawing ##
 // This function draws the entire UI on every frame
 pub fn ui<B: Backend>(f: &mut Frame, app: &mut App) {
+    if app.pager.is_some() {
+        draw_pager(f, app);
+        draw_help(f, app);
+        return;
+    }
+
     // 1. Define main layout
     let chunks = Layout::default()
     .direction(Direction::Vertical)
@@ -27,84 +51,415 @@ pub fn ui<B: Backend>(f: &mut Frame, app: &mut App) {
     let content_chunk = chunks[1];
 
     // 2. Render Header
-    let path_str = app.current_path.to_string_lossy();
-    let header =
-    Paragraph::new(path_str.as_ref()).style(Style::default().bg(Color::Blue).fg(Color::White));
+    let mut header_text = app.current_path.to_string_lossy().into_owned();
+    if let Some(message) = &app.status_message {
+        header_text.push_str(" — ");
+        header_text.push_str(message);
+    }
+    if !app.pending_keys.is_empty() {
+        header_text.push_str(&format!(" — keys: {}", pending_text(&app.pending_keys)));
+    }
+    let header = Paragraph::new(header_text).style(header_style(app));
     f.render_widget(header, header_chunk);
 
     // 3. Define content layout
+    // The outline pane, when shown, takes its room from the Parent and
+    // Current panels (in proportion) so the preview keeps its width
+    let layout = &app.config.layout;
+    let constraints = if app.show_outline {
+        let rest = layout.parent + layout.current - layout.outline;
+        let parent = layout.parent * rest / (layout.parent + layout.current);
+        vec![
+            Constraint::Percentage(parent),
+            Constraint::Percentage(rest - parent),
+            Constraint::Percentage(layout.outline),
+            Constraint::Percentage(layout.preview),
+        ]
+    } else {
+        vec![
+            Constraint::Percentage(layout.parent),
+            Constraint::Percentage(layout.current),
+            Constraint::Percentage(layout.preview),
+        ]
+    };
     let content_chunks = Layout::default()
     .direction(Direction::Horizontal)
-    .constraints([
-        Constraint::Percentage(25),
-                 Constraint::Percentage(40),
-                 Constraint::Percentage(35),
-    ])
+    .constraints(constraints)
     .split(content_chunk);
+    let preview_chunk = content_chunks[content_chunks.len() - 1];
+
+    // Remember where the lists are, for mouse clicks
+    let inner = |area: Rect| area.inner(Margin::new(1, 1));
+    app.parent_area = inner(content_chunks[0]);
+    app.current_area = inner(content_chunks[1]);
+    app.outline_area = if app.show_outline { inner(content_chunks[2]) } else { Rect::default() };
 
     // 4. Render Panel 1: Parent
     let parent_items: Vec<ListItem> = app
     .parent_entries
     .iter()
-    .map(|entry| format_entry_flat(entry)) // Use flat formatter
+    .map(format_entry_flat) // Use flat formatter
     .collect();
     let parent_list = List::new(parent_items)
-    .block(Block::default().borders(Borders::ALL).title("Parent"))
-    .style(Style::default().fg(Color::DarkGray));
+    .block(panel_block(&app.theme, "Parent"))
+    .style(app.theme.parent);
     f.render_stateful_widget(parent_list, content_chunks[0], &mut app.parent_selected);
 
     // 5. Render Panel 2: Current (The main "tree")
-    // CHANGED: Title now shows view mode, items map calls format_entry_tree
-    let title = if app.recursive_view {
-        "Current (Recursive 'e')"
+    // CHANGED: Title now shows view mode and sort order, items map calls format_entry_tree
+    let view = if app.recursive_view { "Recursive" } else { "Flat" };
+    let mut title = format!("Current ({view} 'e') [{}]", app.list_options.sort);
+    if app.is_loading_tree() {
+        title.push_str(&format!(" {} loading", spinner_frame()));
+    }
+    let highlight = app.theme.selection;
+    if let Some(search) = &mut app.file_search {
+        // Results of a search across files replace the directory listing
+        let title = file_search_title(search, app.input_mode);
+        let items: Vec<ListItem> = search.hits.iter().map(|hit| format_hit(hit, &search.root, &app.theme)).collect();
+        let results = List::new(items)
+        .block(panel_block(&app.theme, title))
+        .highlight_style(highlight);
+        f.render_stateful_widget(results, content_chunks[1], &mut search.selected);
     } else {
-        "Current (Flat 'e')"
+        let current_items: Vec<ListItem> = app
+        .current_entries
+        .iter()
+        .map(|entry| format_entry_tree(entry, &app.theme)) // Use tree formatter
+        .collect();
+        let current_list = List::new(current_items)
+        .block(panel_block(&app.theme, title))
+        .highlight_style(highlight);
+        f.render_stateful_widget(current_list, content_chunks[1], &mut app.current_selected);
+    }
+
+    // 6. Render Panel 3: Preview
+    let preview_title = match (&app.preview_content, app.selected_link) {
+        _ if let Some(search) = &app.preview_search => {
+            format!("Preview — {}", search_status(search, app.input_mode))
+        }
+        (PreviewState::Markdown(doc), Some(i)) if i < doc.links().len() => {
+            format!("Preview — {}", link_status(doc, i))
+        }
+        _ => "Preview (Ctrl+j/k to scroll)".to_string(),
     };
-    let current_items: Vec<ListItem> = app
-    .current_entries
-    .iter()
-    .map(|tree_entry| format_entry_tree(tree_entry)) // Use tree formatter
+    let preview_block = panel_block(&app.theme, preview_title);
+    draw_preview(f, app, preview_block, preview_chunk);
+
+    // 7. Render Outline (optional), highlighting the heading in view
+    if app.show_outline {
+        let outline_title = if app.outline_focus.is_some() {
+            "Outline (j/k, Enter, Esc)"
+        } else {
+            "Outline ('[' ']' 'O')"
+        };
+        let outline_block = panel_block(&app.theme, outline_title);
+        match &app.preview_content {
+            PreviewState::Markdown(doc) if !doc.headings().is_empty() => {
+                let items: Vec<ListItem> = doc.headings().iter().map(|heading| format_heading(heading, &app.theme)).collect();
+                let mut state = ListState::default();
+                state.select(app.outline_focus.or(app.current_heading()));
+                let outline = List::new(items).block(outline_block).highlight_style(highlight);
+                f.render_stateful_widget(outline, content_chunks[2], &mut state);
+                app.outline_offset = state.offset();
+            }
+            _ => {
+                f.render_widget(Paragraph::new("No headings").block(outline_block), content_chunks[2]);
+            }
+        }
+    }
+
+    // 8. Render the fuzzy finder over the file panels, leaving the preview
+    // visible so it can follow the highlighted candidate
+    if let Some(finder) = &mut app.finder {
+        let panels = Rect {
+            width: preview_chunk.x - content_chunk.x,
+            ..content_chunk
+        };
+        let area = centered(panels, 90, 80);
+        f.render_widget(Clear, area);
+        let title = if finder.is_indexing() {
+            "Find file (indexing…)".to_string()
+        } else {
+            let limit = if finder.truncated { " (stopped at the entry limit)" } else { "" };
+            format!("Find file — {}/{}{limit}", finder.candidates.len(), finder.paths.len())
+        };
+        let block = panel_block(&app.theme, title);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+        f.render_widget(Paragraph::new(format!("> {}▏", finder.query)), rows[0]);
+        let items: Vec<ListItem> = finder
+        .candidates
+        .iter()
+        .map(|candidate| format_candidate(&finder.paths[candidate.index], &candidate.positions, &app.theme))
+        .collect();
+        let list = List::new(items).highlight_style(highlight);
+        f.render_stateful_widget(list, rows[1], &mut finder.selected);
+    }
+
+    draw_help(f, app);
+}
+
+// The '?' overlay: every action with its keys from the active keymap and the
+// name to rebind it under in the config
+fn draw_help(f: &mut Frame, app: &mut App) {
+    if app.input_mode != InputMode::Help {
+        return;
+    }
+    let area = centered(f.area(), 90, 80);
+    f.render_widget(Clear, area);
+    let block = panel_block(&app.theme, "Keys — the movement keys scroll, any other key closes");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let help = app.keymap.help();
+    let keys_width = help.iter().map(|(keys, _, _)| keys.width()).max().unwrap_or(0).min(28);
+    let lines: Vec<Line> = help
+    .into_iter()
+    .map(|(keys, name, description)| {
+        Line::from(vec![
+            Span::styled(format!("{keys:<keys_width$}  "), Style::new().add_modifier(Modifier::BOLD)),
+            Span::raw(description),
+            Span::styled(format!("  {name}"), app.theme.secondary),
+        ])
+    })
     .collect();
-    let current_list = List::new(current_items)
-    .block(Block::default().borders(Borders::ALL).title(title))
-    .highlight_style(
-        Style::default()
-        .bg(Color::LightBlue)
-        .fg(Color::Black)
-        .add_modifier(Modifier::BOLD),
-    );
-    f.render_stateful_widget(current_list, content_chunks[1], &mut app.current_selected);
 
-    // 6. Render Panel 3: Preview
-    let preview_block = Block::default()
-    .borders(Borders::ALL)
-    .title("Preview (Ctrl+j/k to scroll)");
-    let preview_chunk = content_chunks[2];
+    // Keep the last line at the bottom rather than scrolling past it
+    let last = lines.len().saturating_sub(inner.height as usize);
+    app.help_scroll = app.help_scroll.min(last as u16);
+    let p = Paragraph::new(Text::from(lines)).scroll((app.help_scroll, 0));
+    f.render_widget(p, inner);
+}
+
+// Lay the previewed document out for `area` and draw the part in view
+fn draw_preview(f: &mut Frame, app: &mut App, block: Block, area: Rect) {
+    app.preview_area = block.inner(area);
+
+    // Re-wrap the cached document only if the pane width changed
+    if let PreviewState::Markdown(doc) = &mut app.preview_content
+        && doc.rewrap(app.preview_area.width)
+    {
+        // Match positions are in rendered lines, so find them again
+        app.rerun_preview_search();
+    }
 
     match &app.preview_content {
         PreviewState::None => {
             f.render_widget(
-                Paragraph::new("No item selected").block(preview_block),
-                            preview_chunk,
+                Paragraph::new("No item selected").block(block),
+                            area,
             );
         }
         PreviewState::Text(text) => {
-            let p = Paragraph::new(text.as_str())
-            .block(preview_block)
-            .wrap(ratatui::widgets::Wrap { trim: false })
-            .scroll((app.preview_scroll_offset, 0)); // Use our u16 offset
-            f.render_widget(p, preview_chunk);
+            // Wrapped by hand, like Markdown, so search matches line up
+            let wrapped = wrap_text(text, app.preview_area.width as usize);
+            let lines = visible_lines(app, &wrapped);
+            let p = Paragraph::new(Text::from(lines))
+            .block(block);
+            f.render_widget(p, area);
         }
-        PreviewState::Markdown(content) => {
-            // Use tui_markdown to parse the string into ratatui::text::Text
-
-            let text: Text<'_> = tui_markdown::from_str(content);
-            // Render it as a normal, scrollable Paragraph
-            let p = Paragraph::new(text)
-            .block(preview_block)
-            .wrap(ratatui::widgets::Wrap { trim: false })
-            .scroll((app.preview_scroll_offset, 0));
-            f.render_widget(p, preview_chunk);
+        PreviewState::Markdown(doc) => {
+            // The document is already parsed and wrapped to the pane width,
+            // so each rendered line maps to exactly one scroll step and we
+            // only need to hand the visible slice to the Paragraph
+            let mut lines = visible_lines(app, &doc.text().lines);
+            let start = app.preview_scroll_offset as usize;
+
+            // Highlight the link focused with Tab
+            if let Some(link) = app.selected_link.and_then(|i| doc.links().get(i)) {
+                for region in &link.regions {
+                    if (start..start + lines.len()).contains(&region.line) {
+                        let line = &mut lines[region.line - start];
+                        *line = patch_columns(line, region.start, region.end, app.theme.link_focus);
+                    }
+                }
+            }
+
+            let p = Paragraph::new(Text::from(lines))
+            .block(block);
+            f.render_widget(p, area);
         }
     }
 }
+
+// Pager mode: the document fills the terminal, with a status line below
+// it in place of the header and panels
+fn draw_pager(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+    .direction(Direction::Vertical)
+    .constraints([Constraint::Min(0), Constraint::Length(1)])
+    .split(f.area());
+    app.parent_area = Rect::default();
+    app.current_area = Rect::default();
+    app.outline_area = Rect::default();
+    draw_preview(f, app, Block::default(), chunks[0]);
+
+    let left = if let Some(message) = &app.status_message {
+        message.clone()
+    } else if !app.pending_keys.is_empty() {
+        format!("keys: {}", pending_text(&app.pending_keys))
+    } else if let Some(search) = &app.preview_search {
+        search_status(search, app.input_mode)
+    } else if let (PreviewState::Markdown(doc), Some(i)) = (&app.preview_content, app.selected_link)
+        && i < doc.links().len()
+    {
+        link_status(doc, i)
+    } else {
+        match &app.pager {
+            Some(PagerSource::File(path)) => path.to_string_lossy().into_owned(),
+            Some(PagerSource::Stdin) => "(standard input)".to_string(),
+            None => String::new(),
+        }
+    };
+
+    let (top, bottom, total) = app.pager_position();
+    let position = if total == 0 {
+        "empty".to_string()
+    } else if bottom >= total {
+        format!("lines {top}-{bottom}/{total} (END)")
+    } else {
+        format!("lines {top}-{bottom}/{total} {}%", bottom * 100 / total)
+    };
+
+    // Right-align the position, cutting the left side short if needed
+    let width = chunks[1].width as usize;
+    let room = width.saturating_sub(position.width() + 1);
+    let mut left: String = left
+    .chars()
+    .scan(0, |used, c| {
+        *used += c.width().unwrap_or(0);
+        (*used <= room).then_some(c)
+    })
+    .collect();
+    let padding = width.saturating_sub(left.width() + position.width());
+    left.push_str(&" ".repeat(padding));
+    left.push_str(&position);
+
+    let status = Paragraph::new(left).style(header_style(app));
+    f.render_widget(status, chunks[1]);
+}
+
+// The path bar, and the pager's status line
+fn header_style(app: &App) -> Style {
+    app.theme.header
+}
+
+// A bordered panel in the theme's border colour
+fn panel_block<'a>(theme: &Theme, title: impl Into<Line<'a>>) -> Block<'a> {
+    Block::default()
+    .borders(Borders::ALL)
+    .border_style(theme.border)
+    .title(title)
+}
+
+// Taken from the clock, so every redraw while loading shows the right frame
+fn spinner_frame() -> char {
+    let ticks = SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_millis() / SPINNER_TICK.as_millis());
+    SPINNER[ticks as usize % SPINNER.len()]
+}
+
+// A rectangle of the given percentage size in the middle of `area`
+fn centered(area: Rect, width_percent: u16, height_percent: u16) -> Rect {
+    // In u32, as a wide terminal times a percentage overflows u16
+    let percent_of = |size: u16, percent: u16| (u32::from(size) * u32::from(percent.min(100)) / 100) as u16;
+    let width = percent_of(area.width, width_percent);
+    let height = percent_of(area.height, height_percent);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+// The lines of the preview that fit in the pane at the current scroll
+// offset, with search matches highlighted
+fn visible_lines(app: &App, lines: &[Line<'static>]) -> Vec<Line<'static>> {
+    let start = (app.preview_scroll_offset as usize).min(lines.len());
+    let end = (start + app.preview_area.height as usize).min(lines.len());
+    let mut visible = lines[start..end].to_vec();
+
+    if let Some(search) = &app.preview_search {
+        for (i, m) in search.matches.iter().enumerate() {
+            if !(start..end).contains(&m.line) {
+                continue;
+            }
+            let style = if search.current == Some(i) {
+                app.theme.search_current
+            } else {
+                app.theme.search_match
+            };
+            let line = &mut visible[m.line - start];
+            *line = patch_columns(line, m.start, m.end, style);
+        }
+    }
+    visible
+}
+
+// Describes a search being typed or stepped through, for the preview
+// title or the pager's status line
+fn search_status(search: &PreviewSearch, mode: InputMode) -> String {
+    let mut flags = Vec::new();
+    if search.regex {
+        flags.push("regex");
+    }
+    if search.case_sensitive {
+        flags.push("case");
+    }
+    let flags = if flags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", flags.join(", "))
+    };
+
+    let status = if let Some(error) = &search.error {
+        // Regex errors span several lines; the last one says what is wrong
+        format!("invalid regex: {}", error.lines().last().unwrap_or_default())
+    } else if search.query.is_empty() {
+        "Alt+c case, Alt+r regex".to_string()
+    } else if let Some(i) = search.current {
+        format!("match {}/{}", i + 1, search.matches.len())
+    } else {
+        "no matches".to_string()
+    };
+
+    let hint = if mode == InputMode::PreviewSearch {
+        "Enter to keep, Esc to cancel"
+    } else {
+        "n/N to step, Esc to clear"
+    };
+    format!("/{}{} — {} ({})", search.query, flags, status, hint)
+}
+
+// Describes the link focused with Tab
+fn link_status(doc: &MarkdownDocument, index: usize) -> String {
+    format!(
+        "link {}/{}: {} (Enter to follow)",
+        index + 1,
+        doc.links().len(),
+        doc.links()[index].target
+    )
+}
+
+// Current panel title while a search across files is typed or running
+fn file_search_title(search: &FileSearch, mode: InputMode) -> String {
+    if mode == InputMode::FileSearch {
+        return format!("Find in files: {}▏(Enter to search, Esc to cancel)", search.query);
+    }
+    let hits = search.hits.len();
+    let status = match search.done {
+        _ if search.is_running() => "searching…".to_string(),
+        Some((files, true)) => format!("limit reached after {files} files"),
+        Some((files, false)) => format!("{files} files searched"),
+        None => String::new(),
+    };
+    format!("'{}' — {} hits ({}) Esc to close", search.query, hits, status)
+}
@@ -0,0 +1,308 @@
+use pulldown_cmark::Alignment;
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+
+use crate::ui::markdown::wrap_line;
+use crate::ui::theme::Theme;
+
+// ## GFM Table Layout ##
+
+// Columns are never squeezed below this many cells unless their content is
+// narrower, and never kept wider than MAX_MIN_COLUMN_WIDTH just to avoid
+// splitting a long word
+const MIN_COLUMN_WIDTH: usize = 3;
+const MAX_MIN_COLUMN_WIDTH: usize = 12;
+
+// A pipe table collected from the Markdown event stream
+pub struct Table {
+    alignments: Vec<Alignment>,
+    head: Vec<Line<'static>>,
+    rows: Vec<Vec<Line<'static>>>,
+    // Row and cell currently being filled in
+    row: Vec<Line<'static>>,
+    cell: Line<'static>,
+}
+
+impl Table {
+    pub fn new(alignments: Vec<Alignment>) -> Table {
+        Table {
+            alignments,
+            head: Vec::new(),
+            rows: Vec::new(),
+            row: Vec::new(),
+            cell: Line::default(),
+        }
+    }
+
+    // -- Building --
+
+    pub fn push_span(&mut self, span: Span<'static>) {
+        self.cell.push_span(span);
+    }
+
+    pub fn end_cell(&mut self) {
+        self.row.push(std::mem::take(&mut self.cell));
+    }
+
+    pub fn end_head(&mut self) {
+        self.head = std::mem::take(&mut self.row);
+    }
+
+    pub fn end_row(&mut self) {
+        let row = std::mem::take(&mut self.row);
+        self.rows.push(row);
+    }
+
+    // -- Layout --
+
+    // Lays the table out in at most `width` columns. Cells are wrapped if the
+    // natural layout is too wide; if even the narrowest grid does not fit,
+    // every row is printed as a vertical list of "header: value" records.
+    pub fn render(&self, width: usize, theme: &Theme) -> Vec<Line<'static>> {
+        let columns = self.column_count();
+        if columns == 0 {
+            return Vec::new();
+        }
+
+        // "│ " + " │ " between cells + " │"
+        let overhead = 3 * columns + 1;
+        let natural = (0..columns)
+        .map(|col| self.column_cells(col).map(|cell| cell.width()).max().unwrap_or(0).max(1))
+        .collect::<Vec<_>>();
+        let minimum = (0..columns)
+        .map(|col| {
+            let longest_word = self
+            .column_cells(col)
+            .flat_map(|cell| cell.spans.iter())
+            .flat_map(|span| span.content.split_whitespace())
+            .map(unicode_width::UnicodeWidthStr::width)
+            .max()
+            .unwrap_or(0);
+            natural[col].min(longest_word.clamp(MIN_COLUMN_WIDTH, MAX_MIN_COLUMN_WIDTH))
+        })
+        .collect::<Vec<_>>();
+
+        if minimum.iter().sum::<usize>() + overhead > width {
+            return self.render_records(width, theme);
+        }
+
+        // Shrink the widest columns one cell at a time until the grid fits
+        let mut widths = natural;
+        while widths.iter().sum::<usize>() + overhead > width {
+            let widest = (0..columns)
+            .filter(|&col| widths[col] > minimum[col])
+            .max_by_key(|&col| widths[col]);
+            match widest {
+                Some(col) => widths[col] -= 1,
+                None => break,
+            }
+        }
+
+        self.render_grid(&widths, theme)
+    }
+
+    fn column_count(&self) -> usize {
+        self.rows
+        .iter()
+        .map(Vec::len)
+        .chain([self.head.len(), self.alignments.len()])
+        .max()
+        .unwrap_or(0)
+    }
+
+    fn column_cells(&self, col: usize) -> impl Iterator<Item = &Line<'static>> {
+        std::iter::once(&self.head)
+        .chain(self.rows.iter())
+        .filter_map(move |row| row.get(col))
+    }
+
+    fn render_grid(&self, widths: &[usize], theme: &Theme) -> Vec<Line<'static>> {
+        let border = theme.table_border;
+        let rule = |left: &str, mid: &str, right: &str| {
+            let segments = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+            Line::styled(format!("{left}{}{right}", segments.join(mid)), border)
+        };
+
+        let mut lines = vec![rule("┌", "┬", "┐")];
+        if !self.head.is_empty() {
+            self.render_row(&self.head, widths, theme.table_header, border, &mut lines);
+            lines.push(rule("├", "┼", "┤"));
+        }
+        for row in &self.rows {
+            self.render_row(row, widths, Style::default(), border, &mut lines);
+        }
+        lines.push(rule("└", "┴", "┘"));
+        lines
+    }
+
+    fn render_row(
+        &self,
+        row: &[Line<'static>],
+        widths: &[usize],
+        style: Style,
+        border: Style,
+        lines: &mut Vec<Line<'static>>,
+    ) {
+        // Wrap every cell to its column, then emit as many lines as the
+        // tallest cell needs
+        let empty = Line::default();
+        let wrapped = widths
+        .iter()
+        .enumerate()
+        .map(|(col, &width)| {
+            let mut cell_lines = Vec::new();
+            wrap_line(row.get(col).unwrap_or(&empty), width, &mut cell_lines);
+            cell_lines
+        })
+        .collect::<Vec<_>>();
+        let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+
+        for i in 0..height {
+            let mut spans = vec![Span::styled("│ ", border)];
+            for (col, &width) in widths.iter().enumerate() {
+                if col > 0 {
+                    spans.push(Span::styled(" │ ", border));
+                }
+                let cell = wrapped[col].get(i).unwrap_or(&empty);
+                let alignment = self.alignments.get(col).copied().unwrap_or(Alignment::None);
+                pad_cell(cell, width, alignment, style, &mut spans);
+            }
+            spans.push(Span::styled(" │", border));
+            lines.push(Line::from(spans));
+        }
+    }
+
+    fn render_records(&self, width: usize, theme: &Theme) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        // With no body there are no records; show the header row rather
+        // than nothing at all
+        if self.rows.is_empty() {
+            let mut spans = Vec::new();
+            for (col, cell) in self.head.iter().enumerate() {
+                if col > 0 {
+                    spans.push(Span::styled(" │ ", theme.table_border));
+                }
+                spans.extend(
+                    cell.spans
+                    .iter()
+                    .map(|span| Span::styled(span.content.clone(), theme.table_header.patch(span.style))),
+                );
+            }
+            wrap_line(&Line::from(spans), width, &mut lines);
+            return lines;
+        }
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 {
+                lines.push(Line::styled("─".repeat(width.min(20)), theme.table_border));
+            }
+            for (col, cell) in row.iter().enumerate() {
+                let header = self
+                .head
+                .get(col)
+                .map(line_to_string)
+                .unwrap_or_else(|| format!("Column {}", col + 1));
+                let mut record = Line::from(Span::styled(format!("{header}: "), theme.table_header));
+                record.spans.extend(cell.spans.iter().cloned());
+                wrap_line(&record, width, &mut lines);
+            }
+        }
+        lines
+    }
+}
+
+// Pads a wrapped cell line out to the column width according to its alignment
+fn pad_cell(
+    cell: &Line<'static>,
+    width: usize,
+    alignment: Alignment,
+    style: Style,
+    spans: &mut Vec<Span<'static>>,
+) {
+    let padding = width.saturating_sub(cell.width());
+    let (left, right) = match alignment {
+        Alignment::Right => (padding, 0),
+        Alignment::Center => (padding / 2, padding - padding / 2),
+        Alignment::Left | Alignment::None => (0, padding),
+    };
+    if left > 0 {
+        spans.push(Span::raw(" ".repeat(left)));
+    }
+    spans.extend(
+        cell.spans
+        .iter()
+        .map(|span| Span::styled(span.content.clone(), style.patch(span.style))),
+    );
+    if right > 0 {
+        spans.push(Span::raw(" ".repeat(right)));
+    }
+}
+
+fn line_to_string(line: &Line<'_>) -> String {
+    line.spans.iter().map(|span| span.content.as_ref()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A table with the given header and body rows, all left-aligned
+    fn table(head: &[&str], rows: &[&[&str]]) -> Table {
+        let mut table = Table::new(vec![Alignment::None; head.len()]);
+        for cell in head {
+            table.push_span(Span::raw(cell.to_string()));
+            table.end_cell();
+        }
+        table.end_head();
+        for row in rows {
+            for cell in *row {
+                table.push_span(Span::raw(cell.to_string()));
+                table.end_cell();
+            }
+            table.end_row();
+        }
+        table
+    }
+
+    fn render(table: &Table, width: usize) -> Vec<String> {
+        table.render(width, &Theme::default()).iter().map(Line::to_string).collect()
+    }
+
+    #[test]
+    fn lays_out_a_grid_at_its_natural_width() {
+        let lines = render(&table(&["a", "bb"], &[&["1", "22"]]), 80);
+        assert_eq!(lines, ["┌───┬────┐", "│ a │ bb │", "├───┼────┤", "│ 1 │ 22 │", "└───┴────┘"]);
+    }
+
+    #[test]
+    fn shrinks_the_widest_column_to_fit() {
+        let long = "several short words that wrap";
+        let lines = render(&table(&["key", "value"], &[&["x", long]]), 20);
+        assert!(lines.iter().all(|line| line.chars().count() <= 20), "{lines:#?}");
+        // The narrow column keeps its width; the long cell wraps
+        assert!(lines[1].starts_with("│ key │ value"));
+        assert!(lines.len() > 5);
+        let body: Vec<&str> = lines[3..lines.len() - 1]
+        .iter()
+        .map(|line| line.split('│').nth(2).unwrap().trim())
+        .collect();
+        assert_eq!(body.join(" "), long);
+    }
+
+    #[test]
+    fn falls_back_to_records_when_no_grid_fits() {
+        // The narrowest grid needs 15 columns
+        let lines = render(&table(&["name", "size"], &[&["one", "1"], &["two", "2"]]), 12);
+        assert_eq!(lines, ["name: one", "size: 1", "────────────", "name: two", "size: 2"]);
+    }
+
+    #[test]
+    fn a_table_with_no_body_still_shows_its_header() {
+        let lines = render(&table(&["first", "second", "third"], &[]), 10);
+        assert_eq!(lines, ["first │", "second │", "third"]);
+        // As a grid where there is room
+        let lines = render(&table(&["first", "second", "third"], &[]), 30);
+        assert_eq!(lines[1], "│ first │ second │ third │");
+    }
+}
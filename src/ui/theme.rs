@@ -0,0 +1,509 @@
+use std::{fs, path::Path};
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+use crate::config::{ColorConfig, Config, optional_color, parse_color};
+
+// ## Theme ##
+
+// Every colour the interface uses: the panels around the preview, and the
+// Markdown rendered in it (which the HTML export shares)
+#[derive(Clone, Debug)]
+pub struct Theme {
+    // The built-in or user theme this was loaded as
+    pub name: String,
+
+    // --- Interface ---
+    // The path bar at the top and the pager's status line
+    pub header: Style,
+    // Panel borders
+    pub border: Style,
+    // The selected entry in the lists
+    pub selection: Style,
+    // Entries in the Parent panel
+    pub parent: Style,
+    // Entries in the Current panel, by file type
+    pub directory: Style,
+    pub markdown_file: Style,
+    pub file: Style,
+    // The target shown after a symbolic link, and a link whose target is
+    // missing
+    pub symlink: Style,
+    pub broken_symlink: Style,
+    // Less important text: line numbers of search hits, action names in
+    // the key help
+    pub secondary: Style,
+    // Characters a fuzzy finder query matched
+    pub fuzzy_match: Style,
+    // Top-level headings in the outline pane
+    pub outline_top: Style,
+
+    // --- Markdown ---
+    // Heading styles, indexed by level - 1 (H1..H6)
+    pub headings: [Style; 6],
+    pub emphasis: Style,
+    pub strong: Style,
+    pub strikethrough: Style,
+    pub blockquote: Style,
+    // Inline `code` spans
+    pub code: Style,
+    // Base style of fenced/indented code blocks
+    pub code_block: Style,
+    // The ``` fence lines around a code block
+    pub code_fence: Style,
+    pub link: Style,
+    // Link focused with Tab in the preview
+    pub link_focus: Style,
+    // Matches of a `/` search in the preview, and the one jumped to
+    pub search_match: Style,
+    pub search_current: Style,
+    pub list_marker: Style,
+    pub rule: Style,
+    // Box-drawing borders and header cells of tables
+    pub table_border: Style,
+    pub table_header: Style,
+    // Token colours for syntax-highlighted code blocks
+    pub syntax: SyntaxTheme,
+
+    // --- HTML export ---
+    // The terminal's own colours are unknown, so exported pages are drawn
+    // on these
+    pub page_background: Color,
+    pub page_foreground: Color,
+}
+
+// Colours for each token class the highlighter distinguishes.
+// These are patched on top of `Theme::code_block`.
+#[derive(Clone, Debug)]
+pub struct SyntaxTheme {
+    pub comment: Style,
+    pub keyword: Style,
+    pub string: Style,
+    pub constant: Style,
+    pub function: Style,
+    pub r#type: Style,
+    pub attribute: Style,
+}
+
+// Names accepted by `Theme::named`
+pub const THEME_NAMES: &[&str] = &["dark", "light", "gruvbox"];
+
+// A style as written in a theme file:
+// `link = { fg = "blue", underlined = true }`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleSpec {
+    #[serde(default, deserialize_with = "optional_color")]
+    fg: Option<Color>,
+    #[serde(default, deserialize_with = "optional_color")]
+    bg: Option<Color>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    dim: bool,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default)]
+    underlined: bool,
+    #[serde(default)]
+    reversed: bool,
+    #[serde(default)]
+    crossed_out: bool,
+}
+
+impl StyleSpec {
+    fn style(&self) -> Style {
+        let mut style = Style::new();
+        style.fg = self.fg;
+        style.bg = self.bg;
+        for (on, modifier) in [
+            (self.bold, Modifier::BOLD),
+            (self.dim, Modifier::DIM),
+            (self.italic, Modifier::ITALIC),
+            (self.underlined, Modifier::UNDERLINED),
+            (self.reversed, Modifier::REVERSED),
+            (self.crossed_out, Modifier::CROSSED_OUT),
+        ] {
+            if on {
+                style = style.add_modifier(modifier);
+            }
+        }
+        style
+    }
+}
+
+impl Theme {
+    // A built-in theme by name
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "gruvbox" => Some(Theme::gruvbox()),
+            _ => None,
+        }
+    }
+
+    // A built-in theme, or else a user theme from `themes/NAME.toml` in the
+    // config directory
+    pub fn load(name: &str) -> Result<Theme, String> {
+        if let Some(theme) = Theme::named(name) {
+            return Ok(theme);
+        }
+        let path = Config::themes_dir().map(|dir| dir.join(format!("{name}.toml")));
+        match path {
+            Some(path) if path.is_file() => Theme::from_file(name, &path),
+            _ => Err(format!(
+                "unknown theme '{name}' (available: {})",
+                Theme::available().join(", ")
+            )),
+        }
+    }
+
+    // The built-in themes followed by the user's, in the order the theme key
+    // cycles through them
+    pub fn available() -> Vec<String> {
+        let mut user: Vec<String> = Config::themes_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let is_toml = path.extension().is_some_and(|extension| extension == "toml");
+            let name = path.file_stem()?.to_str()?.to_string();
+            (is_toml && !THEME_NAMES.contains(&name.as_str())).then_some(name)
+        })
+        .collect();
+        user.sort();
+        THEME_NAMES.iter().map(|name| name.to_string()).chain(user).collect()
+    }
+
+    // A user theme: a built-in `base` theme (dark unless given) with the
+    // styles the file lists replaced
+    fn from_file(name: &str, path: &Path) -> Result<Theme, String> {
+        let in_file = |e: String| format!("{}: {e}", path.display());
+        let text = fs::read_to_string(path).map_err(|e| in_file(e.to_string()))?;
+        let mut table: toml::Table = toml::from_str(&text).map_err(|e| in_file(e.to_string()))?;
+
+        let base = match table.remove("base") {
+            Some(toml::Value::String(base)) => base,
+            Some(_) => return Err(in_file("base must be the name of a built-in theme".to_string())),
+            None => "dark".to_string(),
+        };
+        let mut theme = Theme::named(&base).ok_or_else(|| {
+            in_file(format!("base: unknown theme '{base}' (built in: {})", THEME_NAMES.join(", ")))
+        })?;
+        theme.name = name.to_string();
+
+        for (key, value) in table {
+            match key.as_str() {
+                "page_background" | "page_foreground" => {
+                    let color = value
+                    .as_str()
+                    .ok_or_else(|| "expected a colour".to_string())
+                    .and_then(parse_color)
+                    .map_err(|e| in_file(format!("{key}: {e}")))?;
+                    if key == "page_background" {
+                        theme.page_background = color;
+                    } else {
+                        theme.page_foreground = color;
+                    }
+                }
+                "syntax" => {
+                    let toml::Value::Table(syntax) = value else {
+                        return Err(in_file("syntax must be a table of styles".to_string()));
+                    };
+                    for (token, value) in syntax {
+                        let key = format!("syntax.{token}");
+                        theme.set_style(&key, value).map_err(|e| in_file(format!("{key}: {e}")))?;
+                    }
+                }
+                _ => theme.set_style(&key, value).map_err(|e| in_file(format!("{key}: {e}")))?,
+            }
+        }
+        Ok(theme)
+    }
+
+    fn set_style(&mut self, key: &str, value: toml::Value) -> Result<(), String> {
+        let spec: StyleSpec = value.try_into().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let style = self.style_mut(key).ok_or_else(|| "unknown style".to_string())?;
+        *style = spec.style();
+        Ok(())
+    }
+
+    // A style by the name theme files use for it
+    fn style_mut(&mut self, key: &str) -> Option<&mut Style> {
+        let syntax = &mut self.syntax;
+        let style = match key {
+            "header" => &mut self.header,
+            "border" => &mut self.border,
+            "selection" => &mut self.selection,
+            "parent" => &mut self.parent,
+            "directory" => &mut self.directory,
+            "markdown_file" => &mut self.markdown_file,
+            "file" => &mut self.file,
+            "symlink" => &mut self.symlink,
+            "broken_symlink" => &mut self.broken_symlink,
+            "secondary" => &mut self.secondary,
+            "fuzzy_match" => &mut self.fuzzy_match,
+            "outline_top" => &mut self.outline_top,
+            "heading1" => &mut self.headings[0],
+            "heading2" => &mut self.headings[1],
+            "heading3" => &mut self.headings[2],
+            "heading4" => &mut self.headings[3],
+            "heading5" => &mut self.headings[4],
+            "heading6" => &mut self.headings[5],
+            "emphasis" => &mut self.emphasis,
+            "strong" => &mut self.strong,
+            "strikethrough" => &mut self.strikethrough,
+            "blockquote" => &mut self.blockquote,
+            "code" => &mut self.code,
+            "code_block" => &mut self.code_block,
+            "code_fence" => &mut self.code_fence,
+            "link" => &mut self.link,
+            "link_focus" => &mut self.link_focus,
+            "search_match" => &mut self.search_match,
+            "search_current" => &mut self.search_current,
+            "list_marker" => &mut self.list_marker,
+            "rule" => &mut self.rule,
+            "table_border" => &mut self.table_border,
+            "table_header" => &mut self.table_header,
+            "syntax.comment" => &mut syntax.comment,
+            "syntax.keyword" => &mut syntax.keyword,
+            "syntax.string" => &mut syntax.string,
+            "syntax.constant" => &mut syntax.constant,
+            "syntax.function" => &mut syntax.function,
+            "syntax.type" => &mut syntax.r#type,
+            "syntax.attribute" => &mut syntax.attribute,
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    // Apply the `[colors]` config section, which wins over every theme
+    pub fn with_colors(mut self, colors: &ColorConfig) -> Theme {
+        for (style, fg, bg) in [
+            (&mut self.header, colors.header_fg, colors.header_bg),
+            (&mut self.selection, colors.selection_fg, colors.selection_bg),
+            (&mut self.parent, colors.parent_fg, None),
+        ] {
+            style.fg = fg.or(style.fg);
+            style.bg = bg.or(style.bg);
+        }
+        self
+    }
+
+    // For dark terminals, using the terminal's own palette
+    fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            header: Style::new().fg(Color::White).bg(Color::Blue),
+            border: Style::new(),
+            selection: Style::new()
+            .fg(Color::Black)
+            .bg(Color::LightBlue)
+            .add_modifier(Modifier::BOLD),
+            parent: Style::new().fg(Color::DarkGray),
+            directory: Style::new().fg(Color::Cyan),
+            markdown_file: Style::new().fg(Color::White),
+            file: Style::new().fg(Color::Gray),
+            symlink: Style::new().fg(Color::Magenta),
+            broken_symlink: Style::new().fg(Color::LightRed).add_modifier(Modifier::CROSSED_OUT),
+            secondary: Style::new().fg(Color::DarkGray),
+            fuzzy_match: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            outline_top: Style::new().fg(Color::Cyan),
+            headings: [
+                Style::new()
+                .bg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::UNDERLINED),
+                Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                Style::new()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::ITALIC),
+                Style::new().fg(Color::LightCyan).add_modifier(Modifier::ITALIC),
+                Style::new().fg(Color::LightCyan).add_modifier(Modifier::ITALIC),
+                Style::new().fg(Color::LightCyan).add_modifier(Modifier::ITALIC),
+            ],
+            emphasis: Style::new().add_modifier(Modifier::ITALIC),
+            strong: Style::new().add_modifier(Modifier::BOLD),
+            strikethrough: Style::new().add_modifier(Modifier::CROSSED_OUT),
+            blockquote: Style::new().fg(Color::Green),
+            code: Style::new().fg(Color::White).bg(Color::Black),
+            code_block: Style::new().fg(Color::White),
+            code_fence: Style::new().fg(Color::DarkGray),
+            link: Style::new().fg(Color::Blue).add_modifier(Modifier::UNDERLINED),
+            link_focus: Style::new().add_modifier(Modifier::REVERSED),
+            search_match: Style::new().fg(Color::Black).bg(Color::Yellow),
+            search_current: Style::new()
+            .fg(Color::Black)
+            .bg(Color::LightRed)
+            .add_modifier(Modifier::BOLD),
+            list_marker: Style::new().fg(Color::LightBlue),
+            rule: Style::new().fg(Color::DarkGray),
+            table_border: Style::new().fg(Color::DarkGray),
+            table_header: Style::new().add_modifier(Modifier::BOLD),
+            syntax: SyntaxTheme {
+                comment: Style::new().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                keyword: Style::new().fg(Color::Magenta),
+                string: Style::new().fg(Color::Green),
+                constant: Style::new().fg(Color::Yellow),
+                function: Style::new().fg(Color::Blue),
+                r#type: Style::new().fg(Color::Cyan),
+                attribute: Style::new().fg(Color::LightRed),
+            },
+            page_background: Color::Rgb(0x1e, 0x1e, 0x1e),
+            page_foreground: Color::Rgb(0xcc, 0xcc, 0xcc),
+        }
+    }
+
+    // For light terminals. The named colours are too pale on white in most
+    // palettes, so this uses the fixed 256-colour ones.
+    fn light() -> Theme {
+        let blue = Color::Indexed(25);
+        let teal = Color::Indexed(31);
+        let grey = Color::Indexed(245);
+        Theme {
+            name: "light".to_string(),
+            header: Style::new().fg(Color::White).bg(blue),
+            border: Style::new().fg(Color::Indexed(250)),
+            selection: Style::new()
+            .fg(Color::Black)
+            .bg(Color::Indexed(153))
+            .add_modifier(Modifier::BOLD),
+            parent: Style::new().fg(grey),
+            directory: Style::new().fg(blue),
+            markdown_file: Style::new().fg(Color::Indexed(235)),
+            file: Style::new().fg(Color::Indexed(241)),
+            symlink: Style::new().fg(Color::Indexed(90)),
+            broken_symlink: Style::new().fg(Color::Indexed(160)).add_modifier(Modifier::CROSSED_OUT),
+            secondary: Style::new().fg(grey),
+            fuzzy_match: Style::new().fg(Color::Indexed(166)).add_modifier(Modifier::BOLD),
+            outline_top: Style::new().fg(blue),
+            headings: [
+                Style::new()
+                .fg(Color::White)
+                .bg(blue)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::UNDERLINED),
+                Style::new().fg(blue).add_modifier(Modifier::BOLD),
+                Style::new()
+                .fg(blue)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::ITALIC),
+                Style::new().fg(teal).add_modifier(Modifier::ITALIC),
+                Style::new().fg(teal).add_modifier(Modifier::ITALIC),
+                Style::new().fg(teal).add_modifier(Modifier::ITALIC),
+            ],
+            emphasis: Style::new().add_modifier(Modifier::ITALIC),
+            strong: Style::new().add_modifier(Modifier::BOLD),
+            strikethrough: Style::new().add_modifier(Modifier::CROSSED_OUT),
+            blockquote: Style::new().fg(Color::Indexed(28)),
+            code: Style::new().fg(Color::Indexed(88)).bg(Color::Indexed(254)),
+            code_block: Style::new().fg(Color::Indexed(235)),
+            code_fence: Style::new().fg(Color::Indexed(247)),
+            link: Style::new().fg(Color::Indexed(26)).add_modifier(Modifier::UNDERLINED),
+            link_focus: Style::new().add_modifier(Modifier::REVERSED),
+            search_match: Style::new().fg(Color::Black).bg(Color::Indexed(228)),
+            search_current: Style::new()
+            .fg(Color::Black)
+            .bg(Color::Indexed(210))
+            .add_modifier(Modifier::BOLD),
+            list_marker: Style::new().fg(teal),
+            rule: Style::new().fg(Color::Indexed(250)),
+            table_border: Style::new().fg(Color::Indexed(248)),
+            table_header: Style::new().add_modifier(Modifier::BOLD),
+            syntax: SyntaxTheme {
+                comment: Style::new().fg(grey).add_modifier(Modifier::ITALIC),
+                keyword: Style::new().fg(Color::Indexed(127)),
+                string: Style::new().fg(Color::Indexed(28)),
+                constant: Style::new().fg(Color::Indexed(130)),
+                function: Style::new().fg(blue),
+                r#type: Style::new().fg(Color::Indexed(30)),
+                attribute: Style::new().fg(Color::Indexed(160)),
+            },
+            page_background: Color::Rgb(0xff, 0xff, 0xff),
+            page_foreground: Color::Rgb(0x1f, 0x23, 0x28),
+        }
+    }
+
+    // The gruvbox dark palette, in true colour
+    fn gruvbox() -> Theme {
+        let bg = Color::Rgb(0x28, 0x28, 0x28);
+        let bg1 = Color::Rgb(0x3c, 0x38, 0x36);
+        let bg3 = Color::Rgb(0x66, 0x5c, 0x54);
+        let fg = Color::Rgb(0xeb, 0xdb, 0xb2);
+        let fg4 = Color::Rgb(0xa8, 0x99, 0x84);
+        let grey = Color::Rgb(0x92, 0x83, 0x74);
+        let red = Color::Rgb(0xfb, 0x49, 0x34);
+        let green = Color::Rgb(0xb8, 0xbb, 0x26);
+        let yellow = Color::Rgb(0xfa, 0xbd, 0x2f);
+        let blue = Color::Rgb(0x83, 0xa5, 0x98);
+        let purple = Color::Rgb(0xd3, 0x86, 0x9b);
+        let aqua = Color::Rgb(0x8e, 0xc0, 0x7c);
+        let orange = Color::Rgb(0xfe, 0x80, 0x19);
+        Theme {
+            name: "gruvbox".to_string(),
+            header: Style::new().fg(fg).bg(Color::Rgb(0x50, 0x49, 0x45)),
+            border: Style::new().fg(bg3),
+            selection: Style::new().fg(bg).bg(yellow).add_modifier(Modifier::BOLD),
+            parent: Style::new().fg(grey),
+            directory: Style::new().fg(blue),
+            markdown_file: Style::new().fg(fg),
+            file: Style::new().fg(fg4),
+            symlink: Style::new().fg(purple),
+            broken_symlink: Style::new().fg(red).add_modifier(Modifier::CROSSED_OUT),
+            secondary: Style::new().fg(grey),
+            fuzzy_match: Style::new().fg(orange).add_modifier(Modifier::BOLD),
+            outline_top: Style::new().fg(yellow),
+            headings: [
+                Style::new()
+                .fg(bg)
+                .bg(yellow)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::UNDERLINED),
+                Style::new().fg(yellow).add_modifier(Modifier::BOLD),
+                Style::new()
+                .fg(yellow)
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::ITALIC),
+                Style::new().fg(aqua).add_modifier(Modifier::ITALIC),
+                Style::new().fg(aqua).add_modifier(Modifier::ITALIC),
+                Style::new().fg(aqua).add_modifier(Modifier::ITALIC),
+            ],
+            emphasis: Style::new().add_modifier(Modifier::ITALIC),
+            strong: Style::new().add_modifier(Modifier::BOLD),
+            strikethrough: Style::new().add_modifier(Modifier::CROSSED_OUT),
+            blockquote: Style::new().fg(green),
+            code: Style::new().fg(orange).bg(bg1),
+            code_block: Style::new().fg(fg),
+            code_fence: Style::new().fg(bg3),
+            link: Style::new().fg(blue).add_modifier(Modifier::UNDERLINED),
+            link_focus: Style::new().add_modifier(Modifier::REVERSED),
+            search_match: Style::new().fg(bg).bg(yellow),
+            search_current: Style::new().fg(bg).bg(orange).add_modifier(Modifier::BOLD),
+            list_marker: Style::new().fg(blue),
+            rule: Style::new().fg(bg3),
+            table_border: Style::new().fg(bg3),
+            table_header: Style::new().fg(fg).add_modifier(Modifier::BOLD),
+            syntax: SyntaxTheme {
+                comment: Style::new().fg(grey).add_modifier(Modifier::ITALIC),
+                keyword: Style::new().fg(red),
+                string: Style::new().fg(green),
+                constant: Style::new().fg(purple),
+                function: Style::new().fg(aqua),
+                r#type: Style::new().fg(yellow),
+                attribute: Style::new().fg(orange),
+            },
+            page_background: bg,
+            page_foreground: fg,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
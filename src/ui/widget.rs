@@ -1,47 +1,117 @@
 use ratatui::{
-    style::{Color, Style},
+    style::Style,
+    text::{Line, Span},
     widgets::ListItem,
 };
 
 
 use crate::app::state::TreeEntry;
-use std::fs;
+use crate::fs::grep::GrepHit;
+use crate::fs::{LinkTarget, is_dir_entry, is_markdown_file, link_target};
+use crate::ui::markdown::Heading;
+use crate::ui::theme::Theme;
+use std::{fs, path::Path};
 
 // ## 5. UI Helpers ##
 
 // Formats a flat directory entry (for Parent panel)
 
 // NEW: Formats a TreeEntry, adding indentation (for Current panel)
-pub fn format_entry_tree(tree_entry: &TreeEntry) -> ListItem {
+pub fn format_entry_tree<'a>(tree_entry: &'a TreeEntry, theme: &Theme) -> ListItem<'a> {
     let entry = &tree_entry.entry;
     let file_name = entry.file_name().to_string_lossy().to_string();
-    let metadata = entry.metadata().ok();
-    let is_dir = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
-
-    let (icon, style) =
-    if is_dir {
-        ("📁 ", Style::default().fg(Color::Cyan)) // Directory
-    } else {
-        ("📄 ", Style::default().fg(Color::White)) // File
-    };
+    let link = entry_link(entry);
+    let (icon, style) = entry_icon_style(entry, link.as_ref(), theme);
 
     // Add indentation based on depth
     let indent = "  ".repeat(tree_entry.depth);
 
-    ListItem::new(format!("{indent}{icon}{file_name}")).style(style)
+    let mut spans = vec![Span::styled(format!("{indent}{icon}{file_name}"), style)];
+    if let Some(link) = &link {
+        let target_style = if link.broken { theme.broken_symlink } else { theme.symlink };
+        spans.push(Span::styled(link_text(link), target_style));
+    }
+    ListItem::new(Line::from(spans))
 }
 
 
-pub fn format_entry_flat(entry: &fs::DirEntry) -> ListItem {
+// Entries of the Parent panel take the panel's style rather than their own
+pub fn format_entry_flat(entry: &fs::DirEntry) -> ListItem<'_> {
     let file_name = entry.file_name().to_string_lossy().to_string();
-    let metadata = entry.metadata().ok();
-    let is_dir = metadata.as_ref().map(|m| m.is_dir()).unwrap_or(false);
+    let link = entry_link(entry);
+    let icon = if link.as_ref().is_some_and(|link| link.broken) {
+        "🔗 "
+    } else if is_dir_entry(entry) {
+        "📁 "
+    } else {
+        "📄 "
+    };
+    let target = link.as_ref().map(link_text).unwrap_or_default();
 
-    let (icon, style) = if is_dir {
-        ("📁 ", Style::default().fg(Color::Cyan))
+    ListItem::new(format!("{icon}{file_name}{target}"))
+}
+
+// Icon and theme style for an entry's file type. A link gets those of what
+// it points to.
+fn entry_icon_style(entry: &fs::DirEntry, link: Option<&LinkTarget>, theme: &Theme) -> (&'static str, Style) {
+    if link.is_some_and(|link| link.broken) {
+        ("🔗 ", theme.broken_symlink)
+    } else if is_dir_entry(entry) {
+        ("📁 ", theme.directory)
+    } else if is_markdown_file(&entry.path()) {
+        ("📄 ", theme.markdown_file)
     } else {
-        ("📄 ", Style::default().fg(Color::White))
+        ("📄 ", theme.file)
+    }
+}
+
+// Where the entry points, if it is a symbolic link
+fn entry_link(entry: &fs::DirEntry) -> Option<LinkTarget> {
+    entry
+    .file_type()
+    .is_ok_and(|ft| ft.is_symlink())
+    .then(|| link_target(&entry.path()))
+    .flatten()
+}
+
+// Shown after a link's name: " → target", flagged if nothing is there
+fn link_text(link: &LinkTarget) -> String {
+    let broken = if link.broken { " (broken)" } else { "" };
+    format!(" → {}{broken}", link.target.display())
+}
+
+// Formats a heading for the outline pane, indented by level
+pub fn format_heading<'a>(heading: &'a Heading, theme: &Theme) -> ListItem<'a> {
+    let indent = "  ".repeat(heading.level.saturating_sub(1));
+    let style = if heading.level == 1 {
+        theme.outline_top
+    } else {
+        theme.markdown_file
     };
+    ListItem::new(format!("{indent}{}", heading.text)).style(style)
+}
+
+// Formats a search hit as "path:line: snippet", with the path relative to
+// the directory the search ran in
+pub fn format_hit<'a>(hit: &'a GrepHit, root: &Path, theme: &Theme) -> ListItem<'a> {
+    let path = hit.path.strip_prefix(root).unwrap_or(&hit.path);
+    ListItem::new(Line::from(vec![
+        Span::styled(path.to_string_lossy().into_owned(), theme.directory),
+        Span::styled(format!(":{}: ", hit.line + 1), theme.secondary),
+        Span::raw(hit.snippet.as_str()),
+    ]))
+}
 
-    ListItem::new(format!("{icon}{file_name}")).style(style)
+// Formats a finder candidate, highlighting the characters the query matched
+pub fn format_candidate<'a>(path: &str, positions: &[usize], theme: &Theme) -> ListItem<'a> {
+    let matched = theme.fuzzy_match;
+    let spans: Vec<Span> = path
+    .chars()
+    .enumerate()
+    .map(|(i, c)| {
+        let style = if positions.contains(&i) { matched } else { Style::default() };
+        Span::styled(c.to_string(), style)
+    })
+    .collect();
+    ListItem::new(Line::from(spans))
 }